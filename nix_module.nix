@@ -1,6 +1,7 @@
 {
   config,
   lib,
+  pkgs,
   inputs,
   ...
 }:
@@ -18,6 +19,11 @@ in
 		type = types.package;
 	    default = inputs.time-guardian.packages.x86_64-linux.default;
 	  };
+      admin = lib.mkOption {
+        type = types.nullOr types.str;
+        default = null;
+        description = "User to notify when running on a fallback config";
+      };
     };
   };
 
@@ -25,11 +31,16 @@ in
     systemd.services.time-guardian = {
       description = "Screen time control tool";
       after = [ "multi-user.target" ];
+      # For blocking network access
+      path = [ pkgs.nftables ];
 
       serviceConfig = {
         Type = "simple";
+        StateDirectory = "time-guardian";
+        Environment = "TIME_GUARDIAN_STATE_DIR=%S/time-guardian";
         ExecStart = ''
           ${lib.getExe cfg.package} run \
+            ${optionalString (cfg.admin != null) "--admin ${cfg.admin}"}
         '';
       };
     };
@@ -1,3 +1,3 @@
 set -e
 cargo build
-sudo env RUST_LOG=trace target/debug/time-guardian run
+sudo env RUST_LOG=trace HOME="$HOME" target/debug/time-guardian run --dry-run
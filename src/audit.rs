@@ -0,0 +1,23 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use jiff::Zoned;
+use log::info;
+
+use crate::file_io;
+use crate::logging::log_error;
+
+/// Append what a parent or child did to the audit log, one line each
+pub(crate) fn record(event: &str) {
+    info!("Audit: {event}");
+    let line = format!("{} {event}\n", Zoned::now().strftime("%F %T"));
+
+    log_error(
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(file_io::path::audit_log())
+            .and_then(|mut log| log.write_all(line.as_bytes())),
+        "Error while writing to the audit log",
+    );
+}
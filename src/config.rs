@@ -1,131 +1,215 @@
-use std::str::FromStr;
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
 
+use clap::ValueEnum;
 use color_eyre::{eyre::Context, Result};
 use jiff::civil::Date;
-use jiff::Zoned;
-use log::{error, info};
+use jiff::{Unit, Zoned};
+use log::{error, warn};
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
-use serde_with::{serde_as, DurationSecondsWithFrac};
-use strum::{Display, VariantArray};
+use serde_with::{serde_as, Map};
+use strum::Display;
 
+use crate::duration::HumanDuration;
 use crate::file_io;
+use crate::idle::IdleSource;
 use crate::logging::log_error;
+use crate::process::{self, Pattern};
 use crate::time_slot::TimeSlot;
 use crate::user;
+use crate::weekday::{Days, Weekday};
+
+mod command;
+mod init;
+mod profile;
+
+pub(crate) use command::{run_command, ConfigCommand};
+pub(crate) use init::{init, InitArgs};
+use profile::ConfigFile;
+
+pub(crate) const DEFAULT_IDLE_THRESHOLD: Duration = Duration::from_secs(10);
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("User {0} doesn't exist")]
     UserDoesntExist(String),
+    #[error("Profile {0} doesn't exist")]
+    UnknownProfile(String),
+    #[error("Profile {0} is part of an extends cycle")]
+    ProfileCycle(String),
+    #[error("User {user} has no {field}, not set directly or via a profile")]
+    MissingField { user: String, field: String },
+    #[cfg(target_os = "windows")]
+    #[error(
+        "User {user} has on_time_up {option}, which Windows doesn't support"
+    )]
+    UnsupportedEnforcement { user: String, option: String },
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, PartialEq, Clone)]
 pub struct Config(HashMap<String, UserConfig>);
 
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Display,
+)]
+#[strum(serialize_all = "lowercase")]
+pub enum ConfigSource {
+    #[default]
+    Config,
+    Previous,
+    Fallback,
+    #[strum(serialize = "built-in")]
+    Builtin,
+}
+
+/// What to do when not a single config file can be loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum MissingConfigPolicy {
+    /// Guard nobody until a config is written
+    #[default]
+    Open,
+    /// Allow no time at all to every user except the admin
+    Closed,
+}
+
+/// Which config file the daemon is running on, and why
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ConfigState {
+    pub source: ConfigSource,
+    pub error: Option<String>,
+}
+
+impl ConfigState {
+    pub(crate) fn load() -> Result<Self> {
+        file_io::load(file_io::path::config_state())
+    }
+
+    pub(crate) fn store(&self) {
+        log_error(
+            file_io::store(&self, file_io::path::config_state()),
+            "Error while trying to store config state",
+        );
+    }
+
+    pub(crate) fn describe(&self) -> Option<String> {
+        if self.source == ConfigSource::Config {
+            return None;
+        }
+
+        Some(format!(
+            "time-guardian is running on the {} config, loading {} failed: {}",
+            self.source,
+            file_io::path::config(),
+            self.error.as_deref().unwrap_or("unknown error")
+        ))
+    }
+}
+
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[allow(clippy::module_name_repetitions)]
 pub struct UserConfig {
     // TODO: make warnings a user-editable setting
-    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    #[serde_as(as = "HumanDuration")]
     pub short_warning: Duration,
-    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    #[serde_as(as = "HumanDuration")]
     pub long_warning: Duration,
     // TODO: implement a way for rampup to apply to timeslots, not just
     // total_allowed for the day
     pub rampup: Option<Rampup>,
-    // TODO: change to jiff Weekday when it supports serde...
-    days: HashMap<String, DayConfig>,
-}
-
-#[derive(
-    Debug,
-    Serialize,
-    Deserialize,
-    Clone,
-    PartialEq,
-    Eq,
-    Hash,
-    Display,
-    VariantArray,
-)]
-pub enum Weekday {
-    Monday,
-    Tuesday,
-    Wednesday,
-    Thursday,
-    Friday,
-    Saturday,
-    Sunday,
-}
-
-impl FromStr for Weekday {
-    type Err = String;
-
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "mo" | "monday" => Ok(Self::Monday),
-            "tu" | "tuesday" => Ok(Self::Tuesday),
-            "we" | "wednesday" => Ok(Self::Wednesday),
-            "th" | "thursday" => Ok(Self::Thursday),
-            "fr" | "friday" => Ok(Self::Friday),
-            "sa" | "saturday" => Ok(Self::Saturday),
-            "su" | "sunday" => Ok(Self::Sunday),
-            invalid => Err(format!("Invalid weekday string: {invalid}")),
-        }
-    }
-}
-
-impl From<jiff::civil::Weekday> for Weekday {
-    fn from(value: jiff::civil::Weekday) -> Self {
-        match value {
-            jiff::civil::Weekday::Monday => Weekday::Monday,
-            jiff::civil::Weekday::Tuesday => Weekday::Tuesday,
-            jiff::civil::Weekday::Wednesday => Weekday::Wednesday,
-            jiff::civil::Weekday::Thursday => Weekday::Thursday,
-            jiff::civil::Weekday::Friday => Weekday::Friday,
-            jiff::civil::Weekday::Saturday => Weekday::Saturday,
-            jiff::civil::Weekday::Sunday => Weekday::Sunday,
-        }
-    }
+    // Off means monitor only: tracked as usual, but never logged out
+    pub enforce: bool,
+    // Monitor only: how often to remind someone who is over time
+    #[serde_as(as = "Option<HumanDuration>")]
+    pub nag_every: Option<Duration>,
+    // How long to keep someone out after logging them out
+    #[serde_as(as = "Option<HumanDuration>")]
+    pub relogin_cooldown: Option<Duration>,
+    pub breaks: Option<BreakRule>,
+    // Idle for at least this long doesn't count as use
+    #[serde_as(as = "HumanDuration")]
+    pub idle_threshold: Duration,
+    pub idle_source: IdleSource,
+    // Apps with their own daily budget, by category name
+    pub categories: BTreeMap<String, Category>,
+    // Apps like homework that don't count, see `PartialUserConfig`
+    pub allow_list: Vec<Pattern>,
+    // Processes ignored when checking for only allow-listed apps
+    pub background: Vec<Pattern>,
+    pub on_time_up: Enforcement,
+    // When multiple entries contain today, the one with the fewest days
+    // wins, ties go to the first entry in the file
+    #[serde_as(as = "Map<_, _>")]
+    days: Vec<(Days, DayConfig)>,
 }
 
 #[serde_as]
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 struct DayConfig {
-    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    #[serde_as(as = "HumanDuration")]
     total_allowed: Duration,
     time_slots: Option<Vec<TimeSlot>>,
+    // Fields we don't know, kept when writing the config back
+    #[serde(flatten)]
+    other: BTreeMap<String, serde_json::Value>,
 }
 
 impl Default for DayConfig {
     fn default() -> Self {
-        Self {
-            total_allowed: Duration::from_secs(86400),
-            time_slots: Some(vec![TimeSlot::default()]),
-        }
+        Self::new(Duration::from_secs(86400), Some(vec![TimeSlot::default()]))
     }
 }
 
 impl DayConfig {
+    fn new(total_allowed: Duration, time_slots: Option<Vec<TimeSlot>>) -> Self {
+        Self {
+            total_allowed,
+            time_slots,
+            other: BTreeMap::new(),
+        }
+    }
+
     pub(crate) fn apply_rampup_for_today(mut self, rampup: &Rampup) -> Self {
         let today = Zoned::now().datetime().date();
-        if today > rampup.start_date {
-            let n_days: i32 = (today - rampup.start_date).get_days();
-            let old_seconds: u32 = self
-                .total_allowed
-                .as_secs()
-                .try_into()
-                .expect("allowed time < 22Myears");
-
-            let new_seconds: u32 = match rampup.speed {
-                Speed::ConstantSeconds(s) => {
-                    old_seconds.saturating_add_signed(n_days * s)
+        match &rampup.speed {
+            // Dated on their own, so the start date doesn't matter
+            Speed::Milestones(milestones) => {
+                if let Some((_, total)) = milestones.range(..=today).next_back()
+                {
+                    self.total_allowed = *total;
                 }
-                Speed::Percentage(p) => add_percentage(old_seconds, n_days, p),
-            };
-            self.total_allowed = Duration::from_secs(new_seconds.into());
+            }
+            speed if today > rampup.start_date => {
+                let n_days: i32 = (today - rampup.start_date).get_days();
+                let old_seconds: u32 =
+                    self.total_allowed.as_secs().try_into().unwrap_or(u32::MAX);
+
+                let new_seconds: u32 = match speed {
+                    Speed::ConstantSeconds(s) => old_seconds
+                        .saturating_add_signed(n_days.saturating_mul(*s)),
+                    Speed::Percentage(p) => {
+                        add_percentage(old_seconds, n_days, *p)
+                    }
+                    Speed::Stepped { seconds, every } => {
+                        let n_steps =
+                            every.passed_between(rampup.start_date, today);
+                        old_seconds.saturating_add_signed(
+                            n_steps.saturating_mul(*seconds),
+                        )
+                    }
+                    Speed::Milestones(_) => unreachable!("Handled above"),
+                };
+                self.total_allowed = Duration::from_secs(new_seconds.into());
+            }
+            _ => (),
+        }
+        if let Some(round_to) = rampup.round_to {
+            self.total_allowed =
+                round_to_multiple(self.total_allowed, round_to);
         }
         self
     }
@@ -152,29 +236,21 @@ impl UserConfig {
 
     fn todays_config(&self) -> DayConfig {
         let current_weekday: Weekday = Zoned::now().weekday().into();
-        let day_configs: Vec<DayConfig> = self
+        let todays_config = self
             .days
-            .clone()
-            .into_iter()
-            .filter(|(days_str, _)| {
-                to_days(days_str).contains(&current_weekday)
-            })
-            .map(|(_, config)| config)
-            .collect();
-        if day_configs.len() > 1 {
-            println!(
-                "{current_weekday} is in config multiple times,
-                using first occurence"
-            );
-        }
-        if day_configs.len() == 0 {
+            .iter()
+            .filter(|(days, _)| days.contains(current_weekday))
+            // min_by_key returns the first of equal elements
+            .min_by_key(|(days, _)| days.len())
+            .map(|(_, config)| config.clone());
+
+        todays_config.unwrap_or_else(|| {
             println!(
                 "{current_weekday} is not in config!
                 Using default (no blocking)"
             );
-            return DayConfig::default();
-        }
-        day_configs.into_iter().next().expect("Checked for empty")
+            DayConfig::default()
+        })
     }
 
     fn timeslots_right_now(&self) -> Option<Vec<TimeSlot>> {
@@ -194,17 +270,82 @@ impl UserConfig {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+/// After `after` of continuous use, lock the screen for `length`.
+/// Being idle for `length` also counts as a break.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct BreakRule {
+    #[serde_as(as = "HumanDuration")]
+    pub after: Duration,
+    #[serde_as(as = "HumanDuration")]
+    pub length: Duration,
+    #[serde(default = "default_break_warning")]
+    #[serde_as(as = "HumanDuration")]
+    pub warn_before: Duration,
+}
+
+fn default_break_warning() -> Duration {
+    Duration::from_secs(60)
+}
+
+/// What happens when time is up or outside the time slots
+#[serde_as]
+#[derive(
+    Debug, Serialize, Deserialize, Clone, PartialEq, Default, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Enforcement {
+    /// End the whole session
+    #[default]
+    Logout,
+    /// Only end matching apps like games and browsers, with SIGTERM and
+    /// SIGKILL after `grace`
+    Terminate {
+        #[schemars(with = "Vec<String>")]
+        patterns: Vec<Pattern>,
+        #[serde(default = "default_grace")]
+        #[serde_as(as = "HumanDuration")]
+        grace: Duration,
+    },
+    /// Keep the session but block network access, so offline things like
+    /// homework still work
+    BlockNetwork,
+}
+
+fn default_grace() -> Duration {
+    process::DEFAULT_GRACE
+}
+
+/// Apps like games, counted while the user is active and one of them runs.
+/// They are terminated once `allowed` is used up.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct Category {
+    #[schemars(with = "Vec<String>")]
+    pub patterns: Vec<Pattern>,
+    #[serde_as(as = "HumanDuration")]
+    pub allowed: Duration,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct Rampup {
     pub speed: Speed,
+    #[schemars(with = "String")]
     pub start_date: Date,
+    // Round the ramped up time, to avoid values like 1h 07m 13s
+    #[serde(default)]
+    #[serde_as(as = "Option<HumanDuration>")]
+    pub round_to: Option<Duration>,
 }
 
 impl Rampup {
     pub fn clamp_percentage(mut self) -> Self {
         let new_speed = match &self.speed {
             Speed::Percentage(p) => Speed::Percentage(p.clamp(-100.0, 100.0)),
-            other @ Speed::ConstantSeconds(_) => other.clone(),
+            other @ (Speed::ConstantSeconds(_)
+            | Speed::Stepped { .. }
+            | Speed::Milestones(_)) => other.clone(),
         };
 
         self.speed = new_speed;
@@ -212,90 +353,203 @@ impl Rampup {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub enum Speed {
     ConstantSeconds(i32),
     Percentage(f32),
+    /// Add `seconds` once every full interval since the start date
+    Stepped {
+        seconds: i32,
+        every: Interval,
+    },
+    /// Date -> total allowed, the latest milestone reached is used
+    Milestones(
+        #[serde_as(as = "BTreeMap<_, HumanDuration>")] BTreeMap<Date, Duration>,
+    ),
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        let users = match user::list_users() {
-            Ok(users) => users,
-            Err(err) => {
-                error!("Couldn't list users in home: {err:?}");
-                vec!["example_user".to_owned()]
-            }
-        };
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub enum Interval {
+    Days(u16),
+    Weeks(u16),
+    Months(u16),
+}
 
-        let rampup = Rampup {
-            speed: Speed::ConstantSeconds(1),
-            start_date: Date::new(2024, 5, 1).expect("Date exists"),
-        };
-        let days = HashMap::from([(
-            String::from("Mo,Tu,We,Th,Fr,Sa,Su"),
-            DayConfig::default(),
-        )]);
-        let user_config = UserConfig {
-            short_warning: Duration::from_secs(30),
-            long_warning: Duration::from_secs(300),
-            rampup: Some(rampup),
-            days,
+impl Interval {
+    fn passed_between(&self, start: Date, end: Date) -> i32 {
+        let (unit, length) = match self {
+            Interval::Days(n) => (Unit::Day, i32::from(*n)),
+            Interval::Weeks(n) => (Unit::Day, 7 * i32::from(*n)),
+            Interval::Months(n) => (Unit::Month, i32::from(*n)),
         };
+        if length == 0 {
+            return 0;
+        }
 
-        let per_user: HashMap<String, UserConfig> = users
-            .into_iter()
-            .map(|user| (user, user_config.clone()))
-            .collect();
+        let span = start
+            .until((unit, end))
+            .expect("difference between two dates fits in a span");
+        let passed = match unit {
+            Unit::Month => span.get_months(),
+            _ => span.get_days(),
+        };
 
-        Self(per_user)
+        passed / length
     }
 }
 
 impl Config {
-    pub(crate) fn initialize_from_files() -> Self {
-        if match Config::load(file_io::path::TEMPLATE_CONFIG) {
-            Ok(config) => config != Self::default(),
-            Err(_) => true,
-        } {
-            info!("Writing new template config");
-            Config::default().store(file_io::path::TEMPLATE_CONFIG);
-        }
-
-        match Config::load(file_io::path::CONFIG) {
-            Ok(config) => config,
+    pub(crate) fn initialize_from_files(
+        policy: MissingConfigPolicy,
+        admin: Option<&str>,
+    ) -> (Self, ConfigState) {
+        match Config::load(file_io::path::config()) {
+            Ok(config) => (config, ConfigState::default()),
             Err(err) => {
                 error!(
                     "Error while initially loading config, using previous config\nCause: {err:?}"
                 );
-                match Config::load(file_io::path::PREV_CONFIG) {
-                    Ok(config) => config,
+                let error = Some(format!("{err:#}"));
+                match Config::load(file_io::path::prev_config()) {
+                    Ok(config) => (
+                        config,
+                        ConfigState {
+                            source: ConfigSource::Previous,
+                            error,
+                        },
+                    ),
                     Err(err) => {
                         error!("Error while loading previous config on startup, using fallback\nCause: {err:?}");
-                        Config::load(file_io::path::FALLBACK_CONFIG).unwrap()
+                        match Config::load(file_io::path::fallback_config()) {
+                            Ok(config) => (
+                                config,
+                                ConfigState {
+                                    source: ConfigSource::Fallback,
+                                    error,
+                                },
+                            ),
+                            Err(err) => {
+                                error!(
+                                    "No usable config: {}, {} and {} all failed to load, running on the built-in fail-{policy} config. Create one with `time-guardian init`\nCause: {err:?}",
+                                    file_io::path::config(),
+                                    file_io::path::prev_config(),
+                                    file_io::path::fallback_config(),
+                                );
+                                (
+                                    Config::builtin(policy, admin),
+                                    ConfigState {
+                                        source: ConfigSource::Builtin,
+                                        error,
+                                    },
+                                )
+                            }
+                        }
                     }
                 }
             }
         }
     }
 
-    pub(crate) fn reload(self) -> Self {
+    /// The config the daemon is running on, for checks done outside of it.
+    /// Loading the files here could give another one, e.g. when the daemon
+    /// runs on the built-in fail-closed config.
+    pub(crate) fn in_effect() -> Self {
+        Config::load(file_io::path::rampedup()).unwrap_or_else(|_| {
+            // The daemon didn't run yet
+            Config::initialize_from_files(MissingConfigPolicy::default(), None)
+                .0
+        })
+    }
+
+    /// Compiled in, for when no config file can be loaded at all
+    fn builtin(policy: MissingConfigPolicy, admin: Option<&str>) -> Self {
+        let users = match policy {
+            MissingConfigPolicy::Open => Vec::new(),
+            MissingConfigPolicy::Closed => match user::list_users() {
+                Ok(users) => users,
+                Err(err) => {
+                    error!("Couldn't list users in home: {err:?}");
+                    Vec::new()
+                }
+            },
+        };
+
+        let user_config = UserConfig {
+            short_warning: Duration::from_secs(30),
+            long_warning: Duration::from_secs(300),
+            rampup: None,
+            enforce: true,
+            nag_every: None,
+            relogin_cooldown: None,
+            breaks: None,
+            idle_threshold: DEFAULT_IDLE_THRESHOLD,
+            idle_source: IdleSource::default(),
+            categories: BTreeMap::new(),
+            allow_list: Vec::new(),
+            background: process::default_background(),
+            on_time_up: Enforcement::default(),
+            days: vec![(Days::all(), DayConfig::new(Duration::ZERO, None))],
+        };
+
+        Self(
+            users
+                .into_iter()
+                .filter(|user| Some(user.as_str()) != admin)
+                .map(|user| (user, user_config.clone()))
+                .collect(),
+        )
+        .fix_values()
+    }
+
+    pub(crate) fn reload(self, state: &mut ConfigState) -> Self {
         let old_config = self;
 
-        match Config::load(file_io::path::CONFIG) {
+        match Config::load(file_io::path::config()) {
             Ok(new_config) => {
-                Config::store(&new_config, file_io::path::PREV_CONFIG);
+                Config::store(&new_config, file_io::path::prev_config());
+                *state = ConfigState::default();
                 new_config
             }
             Err(err) => {
                 error!("Error loading config: {err:?}");
+                // The old config is the last one that loaded fine, unless
+                // it was already a fallback
+                if state.source == ConfigSource::Config {
+                    state.source = ConfigSource::Previous;
+                }
+                state.error = Some(format!("{err:#}"));
                 old_config
             }
         }
     }
 
+    /// Ask a running `run` to reload the config on its next tick
+    pub(crate) fn request_reload() {
+        log_error(
+            file_io::store(
+                &Zoned::now().to_string(),
+                file_io::path::reload_request(),
+            ),
+            "Error while requesting config reload",
+        );
+    }
+
+    pub(crate) fn reload_requested() -> bool {
+        std::fs::remove_file(file_io::path::reload_request()).is_ok()
+    }
+
     pub fn load(path: &str) -> Result<Self> {
-        let new_config: Self = file_io::load(path)?;
+        Self::from_file(&file_io::load(path)?)
+    }
+
+    fn from_file(config_file: &ConfigFile) -> Result<Self> {
+        for unknown in config_file.unknown_keys() {
+            warn!("Ignoring unknown setting {unknown}");
+        }
+        let new_config = config_file
+            .resolve()
+            .wrap_err("Couldn't resolve profiles and groups")?;
 
         let new_config = new_config.fix_values();
         new_config
@@ -331,6 +585,21 @@ impl Config {
                 return Err(Error::UserDoesntExist(user.clone()));
             };
         }
+        // Would never end the session, so nobody would be held to the
+        // limits
+        #[cfg(target_os = "windows")]
+        for (user, user_config) in self.iter() {
+            let option = match user_config.on_time_up {
+                Enforcement::Logout => continue,
+                // Processes of a user can't be listed yet
+                Enforcement::Terminate { .. } => "terminate",
+                Enforcement::BlockNetwork => "block-network",
+            };
+            return Err(Error::UnsupportedEnforcement {
+                user: user.clone(),
+                option: option.to_owned(),
+            });
+        }
 
         Ok(())
     }
@@ -343,6 +612,10 @@ impl Config {
         &self.0[user]
     }
 
+    pub fn get(&self, user: &str) -> Option<&UserConfig> {
+        self.0.get(user)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (&String, &UserConfig)> + '_ {
         self.0.iter()
     }
@@ -351,8 +624,8 @@ impl Config {
         self.0.into_iter()
     }
 
-    pub fn allowed(&self, user: &str) -> Duration {
-        self.0[user].total_allowed_today()
+    pub fn allowed(&self, user: &str) -> Option<Duration> {
+        self.0.get(user).map(UserConfig::total_allowed_today)
     }
 }
 
@@ -366,7 +639,49 @@ fn add_percentage(old_time: u32, n_days: i32, percentage: f32) -> u32 {
     unrounded.round() as u32
 }
 
-// "Monday,Tuesday" -> vec![WD::Monday, WD::Tuesday]
-fn to_days(str: &str) -> Vec<Weekday> {
-    str.split(',').map(|s| s.parse().unwrap()).collect()
+fn round_to_multiple(duration: Duration, multiple: Duration) -> Duration {
+    let multiple = multiple.as_secs();
+    if multiple == 0 {
+        return duration;
+    }
+
+    let rounded = duration.as_secs().saturating_add(multiple / 2) / multiple;
+    Duration::from_secs(rounded.saturating_mul(multiple))
+}
+
+/// Report settings that are kept in the file but have no effect
+pub(crate) fn warn_unknown_keys(path: &str) {
+    let Ok(config_file) = file_io::load::<ConfigFile>(path) else {
+        return;
+    };
+    for unknown in config_file.unknown_keys() {
+        eprintln!("Warning: unknown setting {unknown}, it has no effect");
+    }
+}
+
+pub(crate) fn check(path: &str) {
+    match Config::load(path) {
+        Ok(config) => {
+            println!(
+                "{}",
+                file_io::to_string(&config).expect(
+                    "Serializing failed, error in serializing format crate"
+                )
+            );
+            warn_unknown_keys(path);
+        }
+        Err(err) => {
+            eprintln!("Config {path} is invalid: {err:?}");
+            std::process::exit(1);
+        }
+    }
+}
+
+pub(crate) fn print_schema() {
+    let schema = ConfigFile::schema();
+    println!(
+        "{}",
+        file_io::to_string(&schema)
+            .expect("Serializing failed, error in serializing format crate")
+    );
 }
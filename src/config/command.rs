@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use clap::{Subcommand, ValueEnum};
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use jiff::civil::Time;
+
+use super::profile::{ConfigFile, PartialUserConfig};
+use super::{Config, DayConfig};
+use crate::duration;
+use crate::file_io;
+use crate::pin::{self, PinHash};
+use crate::status;
+use crate::time_slot::TimeSlot;
+use crate::weekday::Days;
+
+#[derive(Subcommand)]
+pub(crate) enum ConfigCommand {
+    /// Set the total allowed time on some days, e.g. "Mo-Fr 1h30m"
+    SetAllowance {
+        user: String,
+        days: Days,
+        #[arg(value_parser = duration::parse)]
+        allowance: Duration,
+    },
+    /// Add a time slot like 07:00-21:30, optionally with its own budget
+    AddSlot {
+        user: String,
+        days: Days,
+        #[arg(value_parser = parse_slot)]
+        slot: TimeSlot,
+        #[arg(value_parser = duration::parse)]
+        budget: Option<Duration>,
+    },
+    /// Remove a time slot, removing the last one lifts the restriction
+    RemoveSlot {
+        user: String,
+        days: Days,
+        #[arg(value_parser = parse_slot)]
+        slot: TimeSlot,
+    },
+    /// Set how long before logout a warning is sent
+    SetWarning {
+        user: String,
+        warning: Warning,
+        #[arg(value_parser = duration::parse)]
+        before: Duration,
+    },
+    /// Set the PIN that pauses enforcement with `override`, asks for it
+    SetPin { user: String },
+    /// Print the effective config of a user
+    Show { user: String },
+}
+
+#[derive(Clone, ValueEnum)]
+pub(crate) enum Warning {
+    Short,
+    Long,
+}
+
+pub(crate) fn run_command(command: ConfigCommand) {
+    if let Err(err) = run_command_err(command) {
+        eprintln!("{err:#}");
+        std::process::exit(1);
+    }
+}
+
+fn run_command_err(command: ConfigCommand) -> Result<()> {
+    let path = file_io::path::config();
+
+    if let ConfigCommand::Show { user } = &command {
+        let config = Config::load(path)?;
+        let user_config = config
+            .0
+            .get(user)
+            .ok_or(eyre!("{user} is not in the config"))?;
+        println!("{}", file_io::to_string(user_config)?);
+        println!(
+            "allowed today: {}",
+            status::format(user_config.total_allowed_today())
+        );
+        return Ok(());
+    }
+
+    // Kept out of the config, guarded users can read that
+    if let ConfigCommand::SetPin { user } = &command {
+        if Config::load(path)?.get(user).is_none() {
+            return Err(eyre!("{user} is not in the config"));
+        }
+        let pin = pin::read("New PIN: ")?;
+        if pin::read("Repeat PIN: ")? != pin {
+            return Err(eyre!("The PINs don't match"));
+        }
+        pin::store(user, PinHash::new(&pin)?)?;
+        println!("PIN saved to {}", file_io::path::override_pins());
+        return Ok(());
+    }
+
+    let mut config_file: ConfigFile = file_io::load(path)?;
+    config_file.apply(command)?;
+    // Same checks as on load, so the daemon won't reject it
+    Config::from_file(&config_file).wrap_err("Not saving, invalid result")?;
+
+    let serialized = file_io::to_string_for(path, &config_file)?;
+    file_io::write_atomically(path, &serialized)?;
+    Config::request_reload();
+    println!("Config saved to {path}");
+
+    Ok(())
+}
+
+// "07:00-21:30" -> TimeSlot without a budget
+fn parse_slot(input: &str) -> Result<TimeSlot, String> {
+    let invalid = |_| format!("Invalid time slot: {input}, use 07:00-21:30");
+
+    let (start, end) = input
+        .split_once('-')
+        .ok_or(format!("Invalid time slot: {input}, use 07:00-21:30"))?;
+    Ok(TimeSlot {
+        start: start.trim().parse::<Time>().map_err(invalid)?,
+        end: end.trim().parse::<Time>().map_err(invalid)?,
+        time: None,
+    })
+}
+
+impl ConfigFile {
+    fn apply(&mut self, command: ConfigCommand) -> Result<()> {
+        match command {
+            ConfigCommand::SetAllowance {
+                user,
+                days,
+                allowance,
+            } => {
+                let day_configs = self.days_mut(&user)?;
+                match day_configs.iter_mut().find(|(d, _)| *d == days) {
+                    Some((_, day_config)) => {
+                        day_config.total_allowed = allowance;
+                    }
+                    None => day_configs
+                        .push((days, DayConfig::new(allowance, None))),
+                }
+            }
+            ConfigCommand::AddSlot {
+                user,
+                days,
+                slot,
+                budget,
+            } => {
+                let slot = TimeSlot {
+                    time: budget,
+                    ..slot
+                };
+                let slots = self
+                    .day_config_mut(&user, &days)?
+                    .time_slots
+                    .get_or_insert_with(Vec::new);
+                match slots.iter_mut().find(|existing| **existing == slot) {
+                    Some(existing) => *existing = slot,
+                    None => slots.push(slot),
+                }
+            }
+            ConfigCommand::RemoveSlot { user, days, slot } => {
+                let day_config = self.day_config_mut(&user, &days)?;
+                let slots = day_config.time_slots.get_or_insert_with(Vec::new);
+                let n_slots = slots.len();
+                slots.retain(|existing| *existing != slot);
+                if slots.len() == n_slots {
+                    return Err(eyre!("{user} has no such slot on {days}"));
+                }
+                if slots.is_empty() {
+                    day_config.time_slots = None;
+                }
+            }
+            ConfigCommand::SetWarning {
+                user,
+                warning,
+                before,
+            } => {
+                let user_config = self.user_mut(&user)?;
+                match warning {
+                    Warning::Short => user_config.short_warning = Some(before),
+                    Warning::Long => user_config.long_warning = Some(before),
+                }
+            }
+            ConfigCommand::SetPin { .. } | ConfigCommand::Show { .. } => {
+                unreachable!("Doesn't change the config file")
+            }
+        }
+
+        Ok(())
+    }
+
+    fn user_mut(&mut self, user: &str) -> Result<&mut PartialUserConfig> {
+        self.users
+            .get_mut(user)
+            .ok_or(eyre!("{user} is not in the config"))
+    }
+
+    fn days_mut(&mut self, user: &str) -> Result<&mut Vec<(Days, DayConfig)>> {
+        // A user's own days replace the profile's, so start from a copy
+        if self.user_mut(user)?.days.is_none() {
+            let inherited = self
+                .resolve_partial(&self.users[user], &mut Vec::new())?
+                .days;
+            self.user_mut(user)?.days = Some(inherited.unwrap_or_default());
+        }
+
+        Ok(self.user_mut(user)?.days.get_or_insert_with(Vec::new))
+    }
+
+    fn day_config_mut(
+        &mut self,
+        user: &str,
+        days: &Days,
+    ) -> Result<&mut DayConfig> {
+        self.days_mut(user)?
+            .iter_mut()
+            .find(|(d, _)| d == days)
+            .map(|(_, day_config)| day_config)
+            .ok_or(eyre!("{user} has no {days} entry, use set-allowance"))
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use clap::Args;
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use jiff::civil::Time;
+
+use super::profile::{ConfigFile, PartialUserConfig};
+use super::{Config, DayConfig};
+use crate::duration;
+use crate::file_io;
+use crate::status;
+use crate::time_slot::TimeSlot;
+use crate::user;
+use crate::weekday::Days;
+
+const PROFILE: &str = "default";
+
+#[derive(Args)]
+pub(crate) struct InitArgs {
+    /// User to guard, can be repeated, asked for when left out
+    #[arg(long = "user")]
+    users: Vec<String>,
+    /// Allowed time on Monday to Friday
+    #[arg(long, value_parser = duration::parse, default_value = "2h")]
+    weekdays: Duration,
+    /// Allowed time on Saturday and Sunday
+    #[arg(long, value_parser = duration::parse, default_value = "3h")]
+    weekends: Duration,
+    /// Where to write the config, the extension picks json, toml or yaml
+    #[arg(long)]
+    path: Option<String>,
+    /// Overwrite an existing config
+    #[arg(long)]
+    force: bool,
+}
+
+pub(crate) fn init(args: InitArgs) {
+    if let Err(err) = init_err(args) {
+        eprintln!("No config written: {err:#}");
+        std::process::exit(1);
+    }
+}
+
+fn init_err(mut args: InitArgs) -> Result<()> {
+    let path = args
+        .path
+        .take()
+        .unwrap_or_else(|| file_io::path::config().to_owned());
+    if Path::new(&path).exists() && !args.force {
+        return Err(eyre!(
+            "{path} already exists, change it with `time-guardian edit-config` or pass --force"
+        ));
+    }
+
+    if args.users.is_empty() {
+        ask(&mut args)?;
+    }
+    if args.users.is_empty() {
+        return Err(eyre!("No users to guard"));
+    }
+    for user in &args.users {
+        if user == "root" {
+            return Err(eyre!("root is never guarded"));
+        }
+        if !user::exists(user) {
+            return Err(eyre!("User {user} doesn't exist"));
+        }
+    }
+
+    let config_file = initial(&args);
+    Config::from_file(&config_file).wrap_err("Generated config is invalid")?;
+    file_io::store(&config_file, &path)?;
+    Config::request_reload();
+
+    println!("Config saved to {path}");
+    println!(
+        "{} guarded for {} on weekdays and {} on weekends",
+        args.users.join(", "),
+        status::format(args.weekdays),
+        status::format(args.weekends),
+    );
+    Ok(())
+}
+
+fn ask(args: &mut InitArgs) -> Result<()> {
+    if !io::stdin().is_terminal() {
+        return Err(eyre!("Pass the users to guard with --user"));
+    }
+
+    let candidates = user::list_users().unwrap_or_default();
+    let answer = prompt(&format!(
+        "Users to guard, comma separated [{}]",
+        candidates.join(",")
+    ))?;
+    args.users = if answer.is_empty() {
+        candidates
+    } else {
+        answer.split(',').map(|u| u.trim().to_owned()).collect()
+    };
+
+    args.weekdays = ask_duration("Allowed time on weekdays", args.weekdays)?;
+    args.weekends = ask_duration("Allowed time on weekends", args.weekends)?;
+    Ok(())
+}
+
+fn ask_duration(question: &str, default: Duration) -> Result<Duration> {
+    loop {
+        let answer =
+            prompt(&format!("{question} [{}]", status::format(default)))?;
+        if answer.is_empty() {
+            return Ok(default);
+        }
+        match duration::parse(&answer) {
+            Ok(duration) => return Ok(duration),
+            Err(err) => println!("{err}, try e.g. 1h30m"),
+        }
+    }
+}
+
+fn prompt(question: &str) -> Result<String> {
+    print!("{question}: ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().to_owned())
+}
+
+// Every user extends one profile, so later changes can be made in one place
+fn initial(args: &InitArgs) -> ConfigFile {
+    let preset = |allowance, start: Time, end: Time| {
+        DayConfig::new(
+            allowance,
+            Some(vec![TimeSlot {
+                start,
+                end,
+                time: None,
+            }]),
+        )
+    };
+    let days = vec![
+        (
+            "weekdays".parse::<Days>().expect("Valid days"),
+            preset(
+                args.weekdays,
+                Time::constant(7, 0, 0, 0),
+                Time::constant(21, 0, 0, 0),
+            ),
+        ),
+        (
+            "weekends".parse::<Days>().expect("Valid days"),
+            preset(
+                args.weekends,
+                Time::constant(8, 0, 0, 0),
+                Time::constant(22, 0, 0, 0),
+            ),
+        ),
+    ];
+    let profile = PartialUserConfig {
+        short_warning: Some(Duration::from_secs(30)),
+        long_warning: Some(Duration::from_secs(300)),
+        days: Some(days),
+        ..PartialUserConfig::default()
+    };
+
+    let users = args
+        .users
+        .iter()
+        .map(|user| {
+            let partial = PartialUserConfig {
+                extends: Some(PROFILE.to_owned()),
+                ..PartialUserConfig::default()
+            };
+            (user.clone(), partial)
+        })
+        .collect();
+
+    ConfigFile {
+        schema_url: None,
+        profiles: HashMap::from([(PROFILE.to_owned(), profile)]),
+        users,
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+use std::time::Duration;
+
+use color_eyre::{eyre::Context, Result};
+use log::warn;
+use schemars::schema::{RootSchema, Schema};
+use schemars::{schema_for, JsonSchema};
+use serde_derive::{Deserialize, Serialize};
+use serde_with::{serde_as, Map};
+
+use super::{
+    BreakRule, Category, Config, DayConfig, Enforcement, Error, Rampup,
+    UserConfig, DEFAULT_IDLE_THRESHOLD,
+};
+use crate::duration::HumanDuration;
+use crate::idle::IdleSource;
+use crate::process::{self, Pattern};
+use crate::user;
+use crate::weekday::Days;
+
+/// The config as written in the file: users and profiles can extend a
+/// profile, only overriding the fields they set. Keys starting with `@`
+/// apply to all members of that system group.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[schemars(title = "time-guardian config")]
+pub(crate) struct ConfigFile {
+    /// Where editors find the schema, from `time-guardian schema`
+    #[serde(
+        rename = "$schema",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) schema_url: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub(crate) profiles: HashMap<String, PartialUserConfig>,
+    #[serde(flatten)]
+    pub(crate) users: HashMap<String, PartialUserConfig>,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema)]
+pub(crate) struct PartialUserConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) extends: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<HumanDuration>")]
+    pub(crate) short_warning: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<HumanDuration>")]
+    pub(crate) long_warning: Option<Duration>,
+    // Outer None: inherit, Some(None): explicitly no rampup
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "serde_with::rust::double_option"
+    )]
+    #[schemars(with = "Option<Rampup>")]
+    pub(crate) rampup: Option<Option<Rampup>>,
+    /// Set to false to only monitor, never log out (default true)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) enforce: Option<bool>,
+    /// When only monitoring, remind every so often once over time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<HumanDuration>")]
+    pub(crate) nag_every: Option<Duration>,
+    /// How long someone can't log back in after being logged out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<HumanDuration>")]
+    pub(crate) relogin_cooldown: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) breaks: Option<BreakRule>,
+    /// Idle for at least this long doesn't count as use (default 10s)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<HumanDuration>")]
+    pub(crate) idle_threshold: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) idle_source: Option<IdleSource>,
+    /// Apps with their own daily budget, by category name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) categories: Option<BTreeMap<String, Category>>,
+    /// Apps like homework: while nothing but these runs, apart from the
+    /// `background` processes, time is counted as exempt instead of spent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<Vec<String>>")]
+    pub(crate) allow_list: Option<Vec<Pattern>>,
+    /// Session processes like the desktop, shells and audio, which don't
+    /// stop time from being exempt. Defaults to common ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<Vec<String>>")]
+    pub(crate) background: Option<Vec<Pattern>>,
+    /// What happens when time is up, logging out by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) on_time_up: Option<Enforcement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<Map<_, _>>")]
+    pub(crate) days: Option<Vec<(Days, DayConfig)>>,
+    // Fields we don't know, kept when writing the config back
+    #[serde(flatten)]
+    pub(crate) other: BTreeMap<String, serde_json::Value>,
+}
+
+impl PartialUserConfig {
+    fn or(self, base: PartialUserConfig) -> Self {
+        Self {
+            extends: None,
+            short_warning: self.short_warning.or(base.short_warning),
+            long_warning: self.long_warning.or(base.long_warning),
+            rampup: self.rampup.or(base.rampup),
+            enforce: self.enforce.or(base.enforce),
+            nag_every: self.nag_every.or(base.nag_every),
+            relogin_cooldown: self.relogin_cooldown.or(base.relogin_cooldown),
+            breaks: self.breaks.or(base.breaks),
+            idle_threshold: self.idle_threshold.or(base.idle_threshold),
+            idle_source: self.idle_source.or(base.idle_source),
+            categories: self.categories.or(base.categories),
+            allow_list: self.allow_list.or(base.allow_list),
+            background: self.background.or(base.background),
+            on_time_up: self.on_time_up.or(base.on_time_up),
+            days: self.days.or(base.days),
+            other: self.other,
+        }
+    }
+
+    fn complete(self, user: &str) -> Result<UserConfig, Error> {
+        let missing = |field: &str| Error::MissingField {
+            user: user.to_owned(),
+            field: field.to_owned(),
+        };
+
+        Ok(UserConfig {
+            short_warning: self
+                .short_warning
+                .ok_or_else(|| missing("short_warning"))?,
+            long_warning: self
+                .long_warning
+                .ok_or_else(|| missing("long_warning"))?,
+            rampup: self.rampup.flatten(),
+            enforce: self.enforce.unwrap_or(true),
+            nag_every: self.nag_every,
+            relogin_cooldown: self.relogin_cooldown,
+            breaks: self.breaks,
+            idle_threshold: self
+                .idle_threshold
+                .unwrap_or(DEFAULT_IDLE_THRESHOLD),
+            idle_source: self.idle_source.unwrap_or_default(),
+            categories: self.categories.unwrap_or_default(),
+            allow_list: self.allow_list.unwrap_or_default(),
+            background: self
+                .background
+                .unwrap_or_else(process::default_background),
+            on_time_up: self.on_time_up.unwrap_or_default(),
+            days: self.days.ok_or_else(|| missing("days"))?,
+        })
+    }
+}
+
+impl ConfigFile {
+    pub(crate) fn schema() -> RootSchema {
+        let mut schema = schema_for!(ConfigFile);
+        // schemars leaves out the flattened users, any other key is a user
+        schema.schema.object().additional_properties = Some(Box::new(
+            Schema::new_ref("#/definitions/PartialUserConfig".to_owned()),
+        ));
+        schema
+    }
+
+    /// Keys of users and profiles we don't know, likely misspelled, as
+    /// "key in name"
+    pub(crate) fn unknown_keys(&self) -> Vec<String> {
+        self.profiles
+            .iter()
+            .chain(&self.users)
+            .flat_map(|(name, partial)| {
+                partial
+                    .other
+                    .keys()
+                    .map(move |key| format!("{key} in {name}"))
+            })
+            .collect()
+    }
+
+    pub(crate) fn resolve(&self) -> Result<Config> {
+        let mut users = HashMap::new();
+        let mut groups = Vec::new();
+
+        for (key, partial) in &self.users {
+            match key.strip_prefix('@') {
+                Some(group) => groups.push((group, partial)),
+                None => {
+                    users.insert(key.clone(), self.resolve_user(key, partial)?);
+                }
+            }
+        }
+
+        // Users set directly take precedence over their groups, and users
+        // in multiple groups get the alphabetically first one
+        groups.sort_by_key(|(group, _)| *group);
+        for (group, partial) in groups {
+            let members = user::group_members(group)
+                .wrap_err(format!("Couldn't expand group {group}"))?;
+            for member in members {
+                if !user::exists(&member) {
+                    warn!("Skipping {member} of group {group}, doesn't exist");
+                    continue;
+                }
+                if let Entry::Vacant(entry) = users.entry(member) {
+                    let resolved = self.resolve_user(entry.key(), partial)?;
+                    entry.insert(resolved);
+                }
+            }
+        }
+
+        Ok(Config(users))
+    }
+
+    fn resolve_user(
+        &self,
+        user: &str,
+        partial: &PartialUserConfig,
+    ) -> Result<UserConfig, Error> {
+        self.resolve_partial(partial, &mut Vec::new())?
+            .complete(user)
+    }
+
+    pub(crate) fn resolve_partial(
+        &self,
+        partial: &PartialUserConfig,
+        seen: &mut Vec<String>,
+    ) -> Result<PartialUserConfig, Error> {
+        let Some(profile_name) = &partial.extends else {
+            return Ok(partial.clone());
+        };
+
+        if seen.contains(profile_name) {
+            return Err(Error::ProfileCycle(profile_name.clone()));
+        }
+        seen.push(profile_name.clone());
+
+        let profile = self
+            .profiles
+            .get(profile_name)
+            .ok_or_else(|| Error::UnknownProfile(profile_name.clone()))?;
+        let base = self.resolve_partial(profile, seen)?;
+
+        Ok(partial.clone().or(base))
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::Subcommand;
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use jiff::{tz::TimeZone, Timestamp, Zoned};
+use log::{error, info};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::audit;
+use crate::config::Config;
+use crate::file_io;
+use crate::logging::log_error;
+use crate::notification;
+use crate::pin;
+use crate::status;
+use crate::tracker::Tracker;
+use crate::user;
+
+// A client that doesn't send its whole message in time is dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+// Longer messages are cut off and fail to parse
+const MAX_MESSAGE_LEN: u64 = 4096;
+// Wrong PINs in a row after which overrides are refused for a while
+const MAX_PIN_ATTEMPTS: u32 = 3;
+const PIN_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+// Keeps children from flooding the admin with requests
+const MAX_REQUEST_MINUTES: u64 = 24 * 60;
+const MAX_PENDING_REQUESTS: usize = 3;
+
+#[derive(Subcommand)]
+pub(crate) enum RequestsCommand {
+    /// Show the requests waiting for an answer
+    List,
+    /// Give the asked for time, on top of today's allowance
+    Approve { id: u64 },
+    /// Turn the request down
+    Deny { id: u64 },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Message {
+    RequestTime {
+        minutes: u64,
+        reason: Option<String>,
+    },
+    ListRequests,
+    Answer {
+        id: u64,
+        approve: bool,
+    },
+    Override {
+        pin: String,
+        minutes: Option<u64>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Reply {
+    Done(String),
+    Requests(Vec<TimeRequest>),
+    Refused(String),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct TimeRequest {
+    id: u64,
+    user: String,
+    minutes: u64,
+    reason: Option<String>,
+    at: Timestamp,
+}
+
+/// Ask the daemon for more time, as the guarded user
+pub(crate) fn request_time(minutes: u64, reason: Option<String>) {
+    exit_on_err(send(&Message::RequestTime { minutes, reason }));
+}
+
+/// Answer requests for more time, as root or the admin
+pub(crate) fn requests(command: RequestsCommand) {
+    let message = match command {
+        RequestsCommand::List => Message::ListRequests,
+        RequestsCommand::Approve { id } => {
+            Message::Answer { id, approve: true }
+        }
+        RequestsCommand::Deny { id } => Message::Answer { id, approve: false },
+    };
+    exit_on_err(send(&message));
+}
+
+/// Pause enforcement for the user running this, after a parent typed
+/// the PIN. Without `minutes`, or when they run past midnight, for the
+/// rest of the day.
+pub(crate) fn pause(minutes: Option<u64>) {
+    exit_on_err(
+        pin::read("PIN: ")
+            .and_then(|pin| send(&Message::Override { pin, minutes })),
+    );
+}
+
+fn exit_on_err(result: Result<()>) {
+    if let Err(err) = result {
+        eprintln!("{err:#}");
+        std::process::exit(1);
+    }
+}
+
+fn send(message: &Message) -> Result<()> {
+    let path = file_io::path::control_socket();
+    let mut stream = UnixStream::connect(path).wrap_err(format!(
+        "Couldn't reach the daemon at {path}, is it running?"
+    ))?;
+    writeln!(stream, "{}", serde_json::to_string(message)?)?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    match serde_json::from_str(&line)
+        .wrap_err("Unexpected reply from the daemon")?
+    {
+        Reply::Done(text) => println!("{text}"),
+        Reply::Requests(requests) if requests.is_empty() => {
+            println!("No requests waiting");
+        }
+        Reply::Requests(requests) => {
+            for request in requests {
+                println!(
+                    "{}  {}  {} min  asked at {}  {}",
+                    request.id,
+                    request.user,
+                    request.minutes,
+                    status::format_time(request.at),
+                    request.reason.unwrap_or_default()
+                );
+            }
+        }
+        Reply::Refused(reason) => return Err(eyre!(reason)),
+    }
+
+    Ok(())
+}
+
+struct Incoming {
+    sender: String,
+    message: Message,
+    stream: UnixStream,
+}
+
+/// The daemon side, polled from the run loop so it never blocks it
+pub(crate) struct ControlSocket {
+    incoming: Option<Receiver<Incoming>>,
+    requests: Vec<TimeRequest>,
+    // Wrong PINs in a row and when the last one came, per user
+    failed_pins: HashMap<String, (u32, Instant)>,
+}
+
+impl ControlSocket {
+    pub(crate) fn bind() -> Self {
+        let incoming = match listen() {
+            Ok(listener) => {
+                let (sender, receiver) = mpsc::channel();
+                thread::spawn(move || receive(&listener, &sender));
+                Some(receiver)
+            }
+            Err(err) => {
+                error!("Couldn't open control socket: {err:?}");
+                None
+            }
+        };
+
+        let mut requests: Vec<TimeRequest> =
+            if Path::new(file_io::path::time_requests()).exists() {
+                file_io::load(file_io::path::time_requests()).unwrap_or_else(
+                    |err| {
+                        error!("Couldn't load time requests: {err:?}");
+                        Vec::new()
+                    },
+                )
+            } else {
+                Vec::new()
+            };
+        // Asked on a previous day
+        let today = Zoned::now().date();
+        requests.retain(|request| {
+            request.at.to_zoned(TimeZone::system()).date() == today
+        });
+
+        Self {
+            incoming,
+            requests,
+            failed_pins: HashMap::new(),
+        }
+    }
+
+    /// Unanswered requests are for a day that is over
+    pub(crate) fn new_day(&mut self) {
+        self.requests.clear();
+        self.store();
+    }
+
+    /// Answer everyone that connected since the last call
+    pub(crate) fn handle(
+        &mut self,
+        tracker: &mut Tracker,
+        config: &Config,
+        admin: Option<&str>,
+    ) {
+        let Some(incoming) = &self.incoming else {
+            return;
+        };
+
+        let messages: Vec<_> = incoming.try_iter().collect();
+        for message in messages {
+            log_error(
+                self.answer(message, tracker, config, admin),
+                "Error while handling control message",
+            );
+        }
+    }
+
+    fn answer(
+        &mut self,
+        Incoming {
+            sender,
+            message,
+            stream,
+        }: Incoming,
+        tracker: &mut Tracker,
+        config: &Config,
+        admin: Option<&str>,
+    ) -> Result<()> {
+        let is_parent = sender == "root" || Some(sender.as_str()) == admin;
+        let reply = match message {
+            Message::RequestTime { minutes, reason } => {
+                self.request_time(&sender, minutes, reason, config, admin)
+            }
+            Message::ListRequests | Message::Answer { .. } if !is_parent => {
+                Reply::Refused(
+                    "Only root or the admin can answer requests".to_owned(),
+                )
+            }
+            Message::ListRequests => Reply::Requests(self.requests.clone()),
+            Message::Answer { id, approve } => {
+                self.answer_request(&sender, id, approve, tracker)
+            }
+            Message::Override { pin, minutes } => {
+                self.pause(&sender, &pin, minutes, tracker, config)
+            }
+        };
+
+        // A client that stopped reading mustn't hold up the run loop
+        stream.set_write_timeout(Some(READ_TIMEOUT))?;
+        writeln!(&stream, "{}", serde_json::to_string(&reply)?)?;
+        Ok(())
+    }
+
+    fn request_time(
+        &mut self,
+        user: &str,
+        minutes: u64,
+        reason: Option<String>,
+        config: &Config,
+        admin: Option<&str>,
+    ) -> Reply {
+        if config.get(user).is_none() {
+            return Reply::Refused(format!("{user} is not guarded"));
+        }
+        if minutes == 0 {
+            return Reply::Refused("Ask for at least a minute".to_owned());
+        }
+        if minutes > MAX_REQUEST_MINUTES {
+            return Reply::Refused(format!(
+                "Ask for at most {MAX_REQUEST_MINUTES} minutes"
+            ));
+        }
+        let pending = self.requests.iter().filter(|r| r.user == user).count();
+        if pending >= MAX_PENDING_REQUESTS {
+            return Reply::Refused(format!(
+                "You already have {pending} requests waiting for an answer"
+            ));
+        }
+
+        let id = self.requests.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+        info!("{user} asks for {minutes} more minutes (request {id})");
+        if let Some(admin) = admin {
+            let reason =
+                reason.as_ref().map_or(String::new(), |r| format!(": {r}"));
+            notification::notify_user(
+                admin,
+                &format!("{user} asks for {minutes} more minutes{reason}"),
+            );
+        }
+        self.requests.push(TimeRequest {
+            id,
+            user: user.to_owned(),
+            minutes,
+            reason,
+            at: Timestamp::now(),
+        });
+        self.store();
+
+        Reply::Done(format!(
+            "Asked for {minutes} more minutes, a parent has to approve it"
+        ))
+    }
+
+    fn answer_request(
+        &mut self,
+        parent: &str,
+        id: u64,
+        approve: bool,
+        tracker: &mut Tracker,
+    ) -> Reply {
+        let Some(index) = self.requests.iter().position(|r| r.id == id) else {
+            return Reply::Refused(format!("No request with id {id}"));
+        };
+        let request = self.requests.remove(index);
+        self.store();
+        let TimeRequest { user, minutes, .. } = request;
+
+        if !approve {
+            audit::record(&format!(
+                "{parent} denied {minutes} more minutes for {user}"
+            ));
+            notification::notify_user(
+                &user,
+                &format!("Your request for {minutes} more minutes was denied"),
+            );
+            return Reply::Done(format!("Denied {minutes} minutes for {user}"));
+        }
+        if !tracker.counter.contains_key(&user) {
+            return Reply::Refused(format!("{user} is no longer guarded"));
+        }
+
+        audit::record(&format!(
+            "{parent} approved {minutes} more minutes for {user}"
+        ));
+        tracker.grant(&user, Duration::from_secs(minutes.saturating_mul(60)));
+        tracker.store();
+        notification::notify_user(
+            &user,
+            &format!("You got {minutes} more minutes for today"),
+        );
+        Reply::Done(format!("Gave {user} {minutes} more minutes"))
+    }
+
+    fn pause(
+        &mut self,
+        user: &str,
+        pin: &str,
+        minutes: Option<u64>,
+        tracker: &mut Tracker,
+        config: &Config,
+    ) -> Reply {
+        if config.get(user).is_none() {
+            return Reply::Refused(format!("{user} is not guarded"));
+        }
+        let hash = match pin::get(user) {
+            Ok(Some(hash)) => hash,
+            Ok(None) => {
+                return Reply::Refused(format!(
+                    "No override PIN is set for {user}"
+                ))
+            }
+            Err(err) => {
+                return Reply::Refused(format!(
+                    "Couldn't read the override PINs: {err}"
+                ))
+            }
+        };
+        if minutes == Some(0) {
+            return Reply::Refused("Pause for at least a minute".to_owned());
+        }
+
+        let failed = self.failed_pins.get(user).copied();
+        if let Some((failures, last)) = failed {
+            if failures >= MAX_PIN_ATTEMPTS && last.elapsed() < PIN_LOCKOUT {
+                audit::record(&format!(
+                    "override for {user} refused, too many wrong PINs"
+                ));
+                let left = PIN_LOCKOUT - last.elapsed();
+                return Reply::Refused(format!(
+                    "Too many wrong PINs, try again in {} minutes",
+                    left.as_secs().div_ceil(60)
+                ));
+            }
+        }
+        if !hash.verify(pin) {
+            // Counting starts over once a lockout has passed
+            let failures = match failed {
+                Some((failures, last)) if last.elapsed() < PIN_LOCKOUT => {
+                    failures + 1
+                }
+                _ => 1,
+            };
+            self.failed_pins
+                .insert(user.to_owned(), (failures, Instant::now()));
+            audit::record(&format!(
+                "wrong override PIN for {user} ({failures} in a row)"
+            ));
+            return Reply::Refused("Wrong PIN".to_owned());
+        }
+        self.failed_pins.remove(user);
+
+        let end_of_today = match end_of_today() {
+            Ok(end) => end,
+            Err(err) => {
+                return Reply::Refused(format!(
+                    "Couldn't work out the end of the day: {err}"
+                ))
+            }
+        };
+        // An override never lasts past today
+        let until = minutes
+            .and_then(|minutes| minutes.checked_mul(60))
+            .and_then(|secs| {
+                Timestamp::now().checked_add(Duration::from_secs(secs)).ok()
+            })
+            .map_or(end_of_today, |until| until.min(end_of_today));
+        tracker.pause(user, until);
+        tracker.store();
+        audit::record(&format!(
+            "enforcement for {user} paused until {}",
+            until.to_zoned(TimeZone::system()).strftime("%F %T")
+        ));
+
+        Reply::Done(format!(
+            "Enforcement paused until {}",
+            status::format_time(until)
+        ))
+    }
+
+    fn store(&self) {
+        log_error(
+            file_io::store(&self.requests, file_io::path::time_requests()),
+            "Error while trying to store time requests",
+        );
+    }
+}
+
+fn end_of_today() -> Result<Timestamp> {
+    Ok(Zoned::now()
+        .date()
+        .tomorrow()?
+        .to_zoned(TimeZone::system())?
+        .timestamp())
+}
+
+fn listen() -> Result<UnixListener> {
+    let path = file_io::path::control_socket();
+    // Left behind by a previous run
+    if Path::new(path).exists() {
+        fs::remove_file(path)?;
+    }
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    // Guarded users have to be able to connect, who sent what is checked
+    // with the peer credentials
+    fs::set_permissions(path, fs::Permissions::from_mode(0o666))?;
+    Ok(listener)
+}
+
+// Reads clients one at a time on a thread of its own, so a slow client can
+// only hold up other clients and never the run loop
+fn receive(listener: &UnixListener, incoming: &Sender<Incoming>) {
+    for stream in listener.incoming() {
+        match stream.map_err(Into::into).and_then(read_message) {
+            Ok(message) => {
+                if incoming.send(message).is_err() {
+                    return;
+                }
+            }
+            Err(err) => error!("Error while reading control message: {err:?}"),
+        }
+    }
+}
+
+fn read_message(stream: UnixStream) -> Result<Incoming> {
+    let sender = user::name(peer_uid(&stream)?)?;
+
+    let deadline = Instant::now() + READ_TIMEOUT;
+    let too_slow = || eyre!("{sender} took too long to send a message");
+    let mut reader = (&stream).take(MAX_MESSAGE_LEN);
+    let mut line = Vec::new();
+    let mut buf = [0; 512];
+    while !line.contains(&b'\n') {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(too_slow());
+        }
+        stream.set_read_timeout(Some(left))?;
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => line.extend_from_slice(&buf[..read]),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut
+                ) =>
+            {
+                return Err(too_slow());
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    let message = serde_json::from_slice(&line)?;
+
+    Ok(Incoming {
+        sender,
+        message,
+        stream,
+    })
+}
+
+fn peer_uid(stream: &UnixStream) -> Result<u32> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    #[allow(clippy::cast_possible_truncation)] // ucred is tiny
+    let mut len = size_of::<libc::ucred>() as libc::socklen_t;
+    // Safe: credentials and len are valid and len is the size given
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            std::ptr::addr_of_mut!(credentials).cast(),
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(credentials.uid)
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+use std::time::Duration;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{
+    InstanceType, NumberValidation, Schema, SchemaObject, StringValidation,
+    SubschemaValidation,
+};
+use serde::de::{self, Visitor};
+use serde::{Deserializer, Serializer};
+use serde_with::schemars_0_8::JsonSchemaAs;
+use serde_with::{DeserializeAs, SerializeAs};
+
+/// Config durations: stored as (fractional) seconds like before, but also
+/// read from human strings like "1h30m", "90s" or "2h 15min"
+pub struct HumanDuration;
+
+impl SerializeAs<Duration> for HumanDuration {
+    fn serialize_as<S: Serializer>(
+        source: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(source.as_secs_f64())
+    }
+}
+
+impl<'de> DeserializeAs<'de, Duration> for HumanDuration {
+    fn deserialize_as<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        deserializer.deserialize_any(HumanDurationVisitor)
+    }
+}
+
+impl JsonSchemaAs<Duration> for HumanDuration {
+    fn schema_name() -> String {
+        "HumanDuration".to_owned()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        let seconds = SchemaObject {
+            instance_type: Some(InstanceType::Number.into()),
+            number: Some(Box::new(NumberValidation {
+                minimum: Some(0.0),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let human = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(HUMAN_PATTERN.to_owned()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut schema = SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![seconds.into(), human.into()]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        schema.metadata().description = Some(
+            "Seconds, or a duration like \"1h30m\", \"90s\" or \"2h 15min\""
+                .to_owned(),
+        );
+        schema.into()
+    }
+}
+
+// Matches what `parse` accepts
+const HUMAN_PATTERN: &str = r"^\s*([0-9.]+\s*(d|days?|h|hours?|m|mins?|minutes|s|secs?|seconds)\s*)+$|^\s*[0-9.]+\s*$";
+
+struct HumanDurationVisitor;
+
+impl Visitor<'_> for HumanDurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("seconds or a duration like \"1h30m\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Duration, E> {
+        Ok(Duration::from_secs(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Duration, E> {
+        let seconds = u64::try_from(v)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))?;
+        Ok(Duration::from_secs(seconds))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Duration, E> {
+        Duration::try_from_secs_f64(v)
+            .map_err(|_| E::invalid_value(de::Unexpected::Float(v), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Duration, E> {
+        parse(v).map_err(E::custom)
+    }
+}
+
+// "1h30m" -> 5400s, units: d, h, m/min, s/sec, a bare number is seconds
+pub fn parse(input: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {input}");
+
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(seconds) = input.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|_| invalid());
+    }
+
+    let mut total = Duration::default();
+    let mut rest = input;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(invalid)?;
+        let number: f64 = rest[..number_len].parse().map_err(|_| invalid())?;
+        rest = rest[number_len..].trim_start();
+
+        let unit_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit_seconds = match &rest[..unit_len] {
+            "d" | "day" | "days" => 86400.0,
+            "h" | "hour" | "hours" => 3600.0,
+            "m" | "min" | "mins" | "minutes" => 60.0,
+            "s" | "sec" | "secs" | "seconds" => 1.0,
+            _ => return Err(invalid()),
+        };
+        rest = rest[unit_len..].trim_start();
+
+        let part = Duration::try_from_secs_f64(number * unit_seconds)
+            .map_err(|_| invalid())?;
+        total = total.checked_add(part).ok_or_else(invalid)?;
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: u64) -> Result<Duration, String> {
+        Ok(Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(parse("2d"), secs(2 * 86400));
+        assert_eq!(parse("1day"), secs(86400));
+        assert_eq!(parse("3h"), secs(3 * 3600));
+        assert_eq!(parse("1hour"), secs(3600));
+        assert_eq!(parse("5m"), secs(300));
+        assert_eq!(parse("5min"), secs(300));
+        assert_eq!(parse("5minutes"), secs(300));
+        assert_eq!(parse("7s"), secs(7));
+        assert_eq!(parse("7secs"), secs(7));
+        assert_eq!(parse("1.5h"), secs(5400));
+    }
+
+    #[test]
+    fn combined_and_spacing() {
+        assert_eq!(parse("1h30m"), secs(5400));
+        assert_eq!(parse("2h 15min"), secs(8100));
+        assert_eq!(parse("  1 h  30 m  "), secs(5400));
+        assert_eq!(parse("1d1h1m1s"), secs(90061));
+    }
+
+    #[test]
+    fn bare_numbers_are_seconds() {
+        assert_eq!(parse("90"), secs(90));
+        assert_eq!(parse(" 90 "), secs(90));
+        assert_eq!(parse("0.5"), Ok(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn invalid() {
+        for input in ["", "  ", "h", "1x", "1h30", "-5m", "1..5h", "-1", "1h m"]
+        {
+            assert!(parse(input).is_err(), "{input:?} should not parse");
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        assert!(parse("1e30").is_err());
+        assert!(parse("100000000000000000000000d").is_err());
+        assert!(parse("10000000000000000000s 10000000000000000000s").is_err());
+    }
+}
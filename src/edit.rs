@@ -0,0 +1,128 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::process::Command;
+
+use color_eyre::{eyre::eyre, Result};
+
+use crate::config::{self, Config};
+use crate::file_io;
+
+const ANNOTATION: &str = "time-guardian error: ";
+
+pub(crate) fn edit_config() {
+    if let Err(err) = edit_config_err() {
+        eprintln!("Config not changed: {err:#}");
+        std::process::exit(1);
+    }
+}
+
+fn edit_config_err() -> Result<()> {
+    let path = file_io::path::config();
+    let original = fs::read_to_string(path).unwrap_or_default();
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("json");
+    // Next to the config instead of in a shared temp dir, where anyone
+    // could create it first and change it while the editor is open
+    let draft = Path::new(path)
+        .with_file_name(format!(".config-draft.{extension}"))
+        .to_string_lossy()
+        .into_owned();
+    create_draft(&draft)?;
+    let comment = if extension == "json" { "//" } else { "#" };
+
+    let result = edit_draft(path, &draft, &original, comment);
+    // It holds a copy of the config, also when nothing was saved
+    if Path::new(&draft).exists() {
+        fs::remove_file(&draft)?;
+    }
+    result
+}
+
+fn edit_draft(
+    path: &str,
+    draft: &str,
+    original: &str,
+    comment: &str,
+) -> Result<()> {
+    // Errors go at the end, so line numbers in them stay correct
+    let marker = format!("\n{comment} {ANNOTATION}");
+    let mut contents = original.to_owned();
+    let mut error: Option<String> = None;
+    loop {
+        let annotated = match &error {
+            Some(error) => format!("{}{marker}{error}\n", contents.trim_end()),
+            None => contents.clone(),
+        };
+        fs::write(draft, annotated)?;
+        open_editor(draft)?;
+
+        let edited = fs::read_to_string(draft)?;
+        let edited = match edited.find(&marker) {
+            Some(start) => format!("{}\n", &edited[..start]),
+            None => edited,
+        };
+        if edited == original {
+            println!("No changes");
+            return Ok(());
+        }
+        if error.is_some() && edited == contents {
+            return Err(eyre!("errors were not fixed"));
+        }
+
+        fs::write(draft, &edited)?;
+        match Config::load(draft) {
+            Ok(_) => {
+                config::warn_unknown_keys(draft);
+                file_io::write_atomically(path, &edited)?;
+                Config::request_reload();
+                println!("Config saved to {path}, daemon will reload it");
+                return Ok(());
+            }
+            Err(err) => {
+                eprintln!("Config has errors: {err:#}");
+                error = Some(format!("{err:#}"));
+                contents = edited;
+            }
+        }
+    }
+}
+
+// Fresh file only we can write to, removing one left by an earlier edit
+fn create_draft(draft: &str) -> Result<()> {
+    if let Some(dir) = Path::new(draft).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if Path::new(draft).exists() {
+        fs::remove_file(draft)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(target_os = "linux")]
+    options.mode(0o600);
+    options.open(draft)?;
+    Ok(())
+}
+
+// $EDITOR may contain arguments, like "code --wait"
+fn open_editor(path: &str) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    let default_editor = "vi";
+    #[cfg(target_os = "windows")]
+    let default_editor = "notepad";
+
+    let editor = env::var("EDITOR").unwrap_or(default_editor.to_owned());
+    let mut editor = editor.split_whitespace();
+    let program = editor.next().ok_or(eyre!("$EDITOR is empty"))?;
+
+    let status = Command::new(program).args(editor).arg(path).status()?;
+    if !status.success() {
+        return Err(eyre!("editor exited with {status}"));
+    }
+    Ok(())
+}
@@ -1,62 +1,246 @@
 use color_eyre::Result;
 use serde::{de::DeserializeOwned, Serialize};
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 pub(crate) mod path {
-    use const_format::concatcp;
+    use std::path::{Path, PathBuf};
+    use std::sync::OnceLock;
 
     #[cfg(feature = "deploy")]
     #[cfg(target_os = "linux")]
     const CONFIG_BASE: &str = "/etc/time-guardian/";
     #[cfg(feature = "deploy")]
     #[cfg(target_os = "linux")]
-    const STATUS_BASE: &str = "/var/lib/time-guardian/";
+    const STATE_BASE: &str = "/var/lib/time-guardian/";
 
+    // Dev builds only fall back to these without a home directory
     #[cfg(not(feature = "deploy"))]
     #[cfg(target_os = "linux")]
     const CONFIG_BASE: &str = "/etc/time-guardian-dev/";
     #[cfg(not(feature = "deploy"))]
     #[cfg(target_os = "linux")]
-    const STATUS_BASE: &str = "/var/lib/time-guardian-dev/";
+    const STATE_BASE: &str = "/var/lib/time-guardian-dev/";
 
     #[cfg(feature = "deploy")]
     #[cfg(target_os = "windows")]
     const CONFIG_BASE: &str = "C:\\ProgramData\\time-guardian\\";
     #[cfg(feature = "deploy")]
     #[cfg(target_os = "windows")]
-    const STATUS_BASE: &str = "C:\\ProgramData\\time-guardian\\";
+    const STATE_BASE: &str = "C:\\ProgramData\\time-guardian\\";
 
     #[cfg(not(feature = "deploy"))]
     #[cfg(target_os = "windows")]
     const CONFIG_BASE: &str = "C:\\ProgramData\\time-guardian-dev\\";
     #[cfg(not(feature = "deploy"))]
     #[cfg(target_os = "windows")]
-    const STATUS_BASE: &str = "C:\\ProgramData\\time-guardian-dev\\";
+    const STATE_BASE: &str = "C:\\ProgramData\\time-guardian-dev\\";
 
     const CONFIG_NAME: &str = "config.json";
+    const TOML_CONFIG_NAME: &str = "config.toml";
+    const YAML_CONFIG_NAME: &str = "config.yaml";
     const PREV_CONFIG_NAME: &str = "prev-config.json";
     const FALLBACK_CONFIG_NAME: &str = "fallback-config.json";
-    const TEMPLATE_CONFIG_NAME: &str = "template-config.json";
     const STATUS_NAME: &str = "status.json";
     const RAMPEDUP_NAME: &str = "rampedup.json";
+    const RELOAD_REQUEST_NAME: &str = "reload-request";
+    const CONFIG_STATE_NAME: &str = "config-state.json";
+    const HISTORY_NAME: &str = "history.json";
+    const OVERRIDE_PINS_NAME: &str = "override-pins.json";
+    #[cfg(target_os = "linux")]
+    const TIME_REQUESTS_NAME: &str = "time-requests.json";
+    #[cfg(target_os = "linux")]
+    const CONTROL_SOCKET_NAME: &str = "control.sock";
+    #[cfg(target_os = "linux")]
+    const AUDIT_LOG_NAME: &str = "audit.log";
+
+    static PATHS: OnceLock<Paths> = OnceLock::new();
+
+    struct Paths {
+        config: String,
+        toml_config: String,
+        yaml_config: String,
+        prev_config: String,
+        fallback_config: String,
+        status: String,
+        rampedup: String,
+        reload_request: String,
+        config_state: String,
+        history: String,
+        override_pins: String,
+        #[cfg(target_os = "linux")]
+        time_requests: String,
+        #[cfg(target_os = "linux")]
+        control_socket: String,
+        #[cfg(target_os = "linux")]
+        audit_log: String,
+    }
+
+    impl Paths {
+        fn new(config_dir: PathBuf, state_dir: PathBuf) -> Self {
+            let join = |dir: &Path, name| dir.join(name).display().to_string();
+            Self {
+                config: join(&config_dir, CONFIG_NAME),
+                toml_config: join(&config_dir, TOML_CONFIG_NAME),
+                yaml_config: join(&config_dir, YAML_CONFIG_NAME),
+                prev_config: join(&config_dir, PREV_CONFIG_NAME),
+                fallback_config: join(&config_dir, FALLBACK_CONFIG_NAME),
+                status: join(&state_dir, STATUS_NAME),
+                rampedup: join(&state_dir, RAMPEDUP_NAME),
+                reload_request: join(&state_dir, RELOAD_REQUEST_NAME),
+                config_state: join(&state_dir, CONFIG_STATE_NAME),
+                history: join(&state_dir, HISTORY_NAME),
+                override_pins: join(&state_dir, OVERRIDE_PINS_NAME),
+                #[cfg(target_os = "linux")]
+                time_requests: join(&state_dir, TIME_REQUESTS_NAME),
+                #[cfg(target_os = "linux")]
+                control_socket: join(&state_dir, CONTROL_SOCKET_NAME),
+                #[cfg(target_os = "linux")]
+                audit_log: join(&state_dir, AUDIT_LOG_NAME),
+            }
+        }
+    }
+
+    /// Set the directories for this process, `None` picks the default.
+    /// Must be called before any of the paths is used.
+    pub(crate) fn init(
+        config_dir: Option<PathBuf>,
+        state_dir: Option<PathBuf>,
+    ) {
+        let paths = Paths::new(
+            config_dir.unwrap_or_else(default_config_dir),
+            state_dir.unwrap_or_else(default_state_dir),
+        );
+        assert!(PATHS.set(paths).is_ok(), "Paths are initialized only once");
+    }
+
+    fn paths() -> &'static Paths {
+        PATHS.get_or_init(|| {
+            Paths::new(default_config_dir(), default_state_dir())
+        })
+    }
+
+    #[cfg(feature = "deploy")]
+    fn default_config_dir() -> PathBuf {
+        PathBuf::from(CONFIG_BASE)
+    }
+
+    #[cfg(feature = "deploy")]
+    fn default_state_dir() -> PathBuf {
+        PathBuf::from(STATE_BASE)
+    }
+
+    // Dev builds keep everything per user, so no root is needed to try
+    // things out
+    #[cfg(not(feature = "deploy"))]
+    fn default_config_dir() -> PathBuf {
+        xdg_dir("XDG_CONFIG_HOME", ".config")
+            .unwrap_or_else(|| PathBuf::from(CONFIG_BASE))
+    }
+
+    #[cfg(not(feature = "deploy"))]
+    fn default_state_dir() -> PathBuf {
+        xdg_dir("XDG_STATE_HOME", ".local/state")
+            .unwrap_or_else(|| PathBuf::from(STATE_BASE))
+    }
+
+    #[cfg(not(feature = "deploy"))]
+    #[cfg(target_os = "linux")]
+    fn xdg_dir(variable: &str, in_home: &str) -> Option<PathBuf> {
+        let base = std::env::var_os(variable)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                Some(PathBuf::from(std::env::var_os("HOME")?).join(in_home))
+            })?;
+        Some(base.join("time-guardian-dev"))
+    }
+
+    #[cfg(not(feature = "deploy"))]
+    #[cfg(target_os = "windows")]
+    fn xdg_dir(_variable: &str, _in_home: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// The first of config.json, config.toml and config.yaml that exists
+    pub(crate) fn config() -> &'static str {
+        let paths = paths();
+        [&paths.config, &paths.toml_config, &paths.yaml_config]
+            .into_iter()
+            .find(|path| Path::new(path).exists())
+            .unwrap_or(&paths.config)
+    }
+
+    pub(crate) fn prev_config() -> &'static str {
+        &paths().prev_config
+    }
 
-    pub(crate) const CONFIG: &str = concatcp!(CONFIG_BASE, CONFIG_NAME);
-    pub(crate) const PREV_CONFIG: &str =
-        concatcp!(CONFIG_BASE, PREV_CONFIG_NAME);
-    pub(crate) const FALLBACK_CONFIG: &str =
-        concatcp!(CONFIG_BASE, FALLBACK_CONFIG_NAME);
-    pub(crate) const TEMPLATE_CONFIG: &str =
-        concatcp!(CONFIG_BASE, TEMPLATE_CONFIG_NAME);
+    pub(crate) fn fallback_config() -> &'static str {
+        &paths().fallback_config
+    }
+
+    pub(crate) fn status() -> &'static str {
+        &paths().status
+    }
 
-    pub(crate) const STATUS: &str = concatcp!(STATUS_BASE, STATUS_NAME);
-    pub(crate) const RAMPEDUP: &str = concatcp!(STATUS_BASE, RAMPEDUP_NAME);
+    pub(crate) fn rampedup() -> &'static str {
+        &paths().rampedup
+    }
+
+    pub(crate) fn reload_request() -> &'static str {
+        &paths().reload_request
+    }
+
+    pub(crate) fn config_state() -> &'static str {
+        &paths().config_state
+    }
+
+    pub(crate) fn history() -> &'static str {
+        &paths().history
+    }
+
+    pub(crate) fn override_pins() -> &'static str {
+        &paths().override_pins
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(crate) fn time_requests() -> &'static str {
+        &paths().time_requests
+    }
+
+    /// Where the daemon listens for the guarded users and parents
+    #[cfg(target_os = "linux")]
+    pub(crate) fn control_socket() -> &'static str {
+        &paths().control_socket
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(crate) fn audit_log() -> &'static str {
+        &paths().audit_log
+    }
+}
+
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    fn of(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("yaml" | "yml") => Format::Yaml,
+            _ => Format::Json,
+        }
+    }
 }
 
 pub(crate) fn store(
     object: &impl Serialize,
     path: &str,
 ) -> Result<(), std::io::Error> {
-    let serialized = to_string(&object)
+    let serialized = to_string_for(path, &object)
         .expect("Serializing failed, error in serializing format crate");
 
     if !PathBuf::from(path)
@@ -74,9 +258,98 @@ pub(crate) fn store(
     Ok(())
 }
 
+/// Like `store`, but only the owner can read the file
+pub(crate) fn store_private(
+    object: &impl Serialize,
+    path: &str,
+) -> Result<(), std::io::Error> {
+    let serialized = to_string_for(path, &object)
+        .expect("Serializing failed, error in serializing format crate");
+
+    if let Some(dir) = Path::new(path).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files
+        if Path::new(path).exists() {
+            std::fs::set_permissions(
+                path,
+                std::fs::Permissions::from_mode(0o600),
+            )?;
+        }
+    }
+    options.open(path)?.write_all(serialized.as_bytes())
+}
+
+/// Write to a temporary file next to `path`, then rename over it, so
+/// readers never see a half written file
+pub(crate) fn write_atomically(
+    path: &str,
+    contents: &str,
+) -> Result<(), std::io::Error> {
+    let temporary = format!("{path}.tmp");
+    // Left by an earlier write that failed, the mode only applies to new
+    // files
+    if Path::new(&temporary).exists() {
+        std::fs::remove_file(&temporary)?;
+    }
+    // Keep the mode and owner an admin gave the original
+    let original = std::fs::metadata(path).ok();
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(target_os = "linux")]
+    if let Some(original) = &original {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(original.permissions().mode());
+    }
+    let mut file = options.open(&temporary)?;
+    if let Some(original) = &original {
+        // The umask may have taken bits off
+        file.set_permissions(original.permissions())?;
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if file.metadata()?.uid() != original.uid()
+                || file.metadata()?.gid() != original.gid()
+            {
+                std::os::unix::fs::fchown(
+                    &file,
+                    Some(original.uid()),
+                    Some(original.gid()),
+                )?;
+            }
+        }
+    }
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&temporary, path)
+}
+
 pub(crate) fn load<T: DeserializeOwned>(path: &str) -> Result<T> {
     let data = std::fs::read_to_string(path)?;
-    from_str(&data)
+    match Format::of(path) {
+        Format::Json => from_str(&data),
+        Format::Toml => Ok(toml::from_str(&data)?),
+        Format::Yaml => Ok(serde_yaml_ng::from_str(&data)?),
+    }
+}
+
+/// Serialize in the format belonging to the extension of `path`
+pub(crate) fn to_string_for<T: Serialize>(
+    path: &str,
+    object: &T,
+) -> Result<String> {
+    match Format::of(path) {
+        Format::Json => to_string(object),
+        Format::Toml => Ok(toml::to_string_pretty(object)?),
+        Format::Yaml => Ok(serde_yaml_ng::to_string(object)?),
+    }
 }
 
 pub(crate) fn from_str<T: DeserializeOwned>(input: &str) -> Result<T> {
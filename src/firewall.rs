@@ -0,0 +1,141 @@
+use std::collections::BTreeSet;
+#[cfg(target_os = "linux")]
+use std::io::{ErrorKind, Write};
+#[cfg(target_os = "linux")]
+use std::process::{Command, Stdio};
+
+use color_eyre::Result;
+#[allow(unused_imports)]
+use log::{error, info};
+use thiserror::Error;
+
+use crate::config::Config;
+#[cfg(target_os = "linux")]
+use crate::user;
+
+#[cfg(target_os = "linux")]
+const TABLE: &str = "inet time_guardian";
+
+#[derive(Error, Debug)]
+enum Error {
+    #[cfg(target_os = "linux")]
+    #[error("nft failed: {0}")]
+    Nft(String),
+    #[cfg(target_os = "windows")]
+    #[error("Blocking the network is not supported on Windows")]
+    Unsupported,
+}
+
+/// Blocks network access of users with an nftables table of our own,
+/// loopback keeps working
+#[derive(Default)]
+pub(crate) struct Firewall {
+    blocked: BTreeSet<String>,
+}
+
+impl Firewall {
+    /// Returns whether anything changed, a failed change is tried again
+    /// on the next call
+    pub(crate) fn set_blocked(&mut self, user: &str, block: bool) -> bool {
+        let mut blocked = self.blocked.clone();
+        let changed = if block {
+            blocked.insert(user.to_owned())
+        } else {
+            blocked.remove(user)
+        };
+        if !changed || !self.replace(blocked) {
+            return false;
+        }
+
+        if block {
+            info!("Blocked network access of {user}");
+        } else {
+            info!("Unblocked network access of {user}");
+        }
+        true
+    }
+
+    /// Unblock users that are no longer in the config
+    pub(crate) fn keep_only(&mut self, config: &Config) {
+        let blocked: BTreeSet<String> = self
+            .blocked
+            .iter()
+            .filter(|user| config.get(user).is_some())
+            .cloned()
+            .collect();
+        if blocked != self.blocked && self.replace(blocked) {
+            info!("Unblocked network access of users no longer guarded");
+        }
+    }
+
+    /// Remove our rules, including any left by a previous run
+    pub(crate) fn clear(&mut self) {
+        self.replace(BTreeSet::new());
+    }
+
+    fn replace(&mut self, blocked: BTreeSet<String>) -> bool {
+        match apply(&blocked) {
+            Ok(()) => {
+                self.blocked = blocked;
+                true
+            }
+            Err(err) => {
+                error!("Couldn't update network rules: {err:?}");
+                false
+            }
+        }
+    }
+}
+
+// Replaces the whole table in one transaction, so there is no moment with
+// half the rules
+#[cfg(target_os = "linux")]
+fn apply(blocked: &BTreeSet<String>) -> Result<()> {
+    // Adding first makes the delete work when the table doesn't exist yet
+    let mut ruleset = format!("table {TABLE} {{}}\ndelete table {TABLE}\n");
+    if !blocked.is_empty() {
+        ruleset.push_str(&format!(
+            "table {TABLE} {{\n\
+             \tchain output {{\n\
+             \t\ttype filter hook output priority 0; policy accept;\n\
+             \t\toifname \"lo\" accept\n"
+        ));
+        for user in blocked {
+            let uid = user::uid(user)?;
+            ruleset.push_str(&format!("\t\tmeta skuid {uid} reject\n"));
+        }
+        ruleset.push_str("\t}\n}\n");
+    }
+
+    let mut nft = match Command::new("nft")
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        // Without nftables there are no rules to remove
+        Err(err) if err.kind() == ErrorKind::NotFound && blocked.is_empty() => {
+            return Ok(());
+        }
+        nft => nft?,
+    };
+    nft.stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(ruleset.as_bytes())?;
+    let output = nft.wait_with_output()?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Nft(err.trim().to_owned()).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn apply(blocked: &BTreeSet<String>) -> Result<()> {
+    if blocked.is_empty() {
+        return Ok(());
+    }
+    Err(Error::Unsupported.into())
+}
@@ -0,0 +1,108 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Duration;
+
+use color_eyre::Result;
+use jiff::civil::Date;
+use log::error;
+use serde_derive::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSecondsWithFrac};
+
+use crate::file_io;
+use crate::logging::log_error;
+use crate::status;
+use crate::tracker::Tracker;
+
+/// Time spent per user on past days, added to when a day is over
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub(crate) struct History(BTreeMap<Date, HashMap<String, DayRecord>>);
+
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct DayRecord {
+    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    pub(crate) spent: Duration,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[serde_as(as = "BTreeMap<_, DurationSecondsWithFrac<f64>>")]
+    pub(crate) categories: BTreeMap<String, Duration>,
+    #[serde(default, skip_serializing_if = "Duration::is_zero")]
+    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    pub(crate) exempt: Duration,
+}
+
+impl History {
+    fn load() -> Result<Self> {
+        if !Path::new(file_io::path::history()).exists() {
+            return Ok(Self::default());
+        }
+        file_io::load(file_io::path::history())
+    }
+
+    /// Add the day of `tracker`, replacing what was recorded for it before
+    pub(crate) fn record(tracker: &Tracker) {
+        let mut history = match Self::load() {
+            Ok(history) => history,
+            Err(err) => {
+                // Don't overwrite what we can't read
+                error!("Couldn't load history, not recording: {err:?}");
+                return;
+            }
+        };
+
+        let day = tracker
+            .counter
+            .iter()
+            .map(|(user, counter)| {
+                let record = DayRecord {
+                    spent: counter.total_spent,
+                    categories: counter.categories.clone(),
+                    exempt: counter.exempt,
+                };
+                (user.clone(), record)
+            })
+            .collect();
+        history.0.insert(tracker.date, day);
+
+        log_error(
+            file_io::store(&history, file_io::path::history()),
+            "Error while trying to store history",
+        );
+    }
+}
+
+pub(crate) fn print(user: &str, days: usize) {
+    let history = match History::load() {
+        Ok(history) => history,
+        Err(err) => {
+            eprintln!("Couldn't load history: {err:#}");
+            std::process::exit(1);
+        }
+    };
+
+    let records: Vec<_> = history
+        .0
+        .iter()
+        .filter_map(|(date, day)| Some((date, day.get(user)?)))
+        .collect();
+    if records.is_empty() {
+        println!("No history for {user}");
+        return;
+    }
+
+    for (date, record) in records.iter().rev().take(days).rev() {
+        let mut details: Vec<String> = record
+            .categories
+            .iter()
+            .map(|(name, spent)| format!("{name} {}", status::format(*spent)))
+            .collect();
+        if !record.exempt.is_zero() {
+            details.push(format!("exempt {}", status::format(record.exempt)));
+        }
+        let line = format!(
+            "{date}  {}  {}",
+            status::format(record.spent),
+            details.join("  ")
+        );
+        println!("{}", line.trim_end());
+    }
+}
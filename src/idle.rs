@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use std::time::SystemTime;
+
+#[allow(unused_imports)]
+use log::{error, warn};
+use schemars::JsonSchema;
+use serde_derive::{Deserialize, Serialize};
+use strum::Display;
+use thiserror::Error;
+
+// Failures in a row after which the other source is used
+const MAX_FAILURES: usize = 3;
+
+#[derive(Error, Debug)]
+enum Error {
+    #[cfg(target_os = "linux")]
+    #[error("Unexpected error from loginctl")]
+    Command(#[from] std::io::Error),
+    #[cfg(target_os = "linux")]
+    #[error("Utf8 parsing error")]
+    Utf8(#[from] core::str::Utf8Error),
+    #[cfg(target_os = "linux")]
+    #[error("Unexpected error from loginctl: {0}")]
+    Loginctl(String),
+    #[cfg(target_os = "linux")]
+    #[error("Couldn't parse loginctl output: {0}")]
+    Parse(String),
+    #[cfg(target_os = "linux")]
+    #[error("Break enforcer: {0}")]
+    BreakEnforcer(String),
+}
+
+/// Where to read how long a user has been idle
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Default,
+    Display,
+    JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum IdleSource {
+    /// Input on any device, from the break-enforcer daemon
+    #[default]
+    BreakEnforcer,
+    /// IdleHint and IdleSinceHint of systemd-logind
+    Logind,
+}
+
+impl IdleSource {
+    fn fallback(self) -> Self {
+        match self {
+            IdleSource::BreakEnforcer => IdleSource::Logind,
+            IdleSource::Logind => IdleSource::BreakEnforcer,
+        }
+    }
+}
+
+pub(crate) struct IdleMonitor {
+    #[cfg(target_os = "linux")]
+    break_enforcer: Result<break_enforcer::Api, break_enforcer::Error>,
+    failures: HashMap<IdleSource, usize>,
+    // Looked up at most once per tick, `None` until then
+    #[cfg(target_os = "linux")]
+    foreground: Option<Option<String>>,
+}
+
+impl IdleMonitor {
+    pub(crate) fn new() -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            break_enforcer: break_enforcer::Api::new(),
+            failures: HashMap::new(),
+            #[cfg(target_os = "linux")]
+            foreground: None,
+        }
+    }
+
+    /// Forget what was looked up during the previous tick
+    #[cfg_attr(target_os = "windows", allow(clippy::unused_self))]
+    pub(crate) fn next_tick(&mut self) {
+        #[cfg(target_os = "linux")]
+        {
+            self.foreground = None;
+        }
+    }
+
+    /// Idle time of `user` from `source`, or from the other source once
+    /// `source` keeps failing. Defaults to 0 idle = active.
+    pub(crate) fn idle_time(
+        &mut self,
+        user: &str,
+        source: IdleSource,
+    ) -> Duration {
+        // Break enforcer sees all input, which only goes to the user in
+        // front of the screen
+        #[cfg(target_os = "linux")]
+        if source == IdleSource::BreakEnforcer
+            && self
+                .foreground()
+                .is_some_and(|foreground| foreground != user)
+        {
+            return Duration::MAX;
+        }
+
+        if let Some(idle_time) = self.read(user, source) {
+            return idle_time;
+        }
+        if self.failures[&source] < MAX_FAILURES {
+            return Duration::default();
+        }
+
+        self.read(user, source.fallback()).unwrap_or_default()
+    }
+
+    fn read(&mut self, user: &str, source: IdleSource) -> Option<Duration> {
+        let result = match source {
+            IdleSource::BreakEnforcer => self.break_enforcer_idle(),
+            IdleSource::Logind => logind_idle(user),
+        };
+
+        let failures = self.failures.entry(source).or_default();
+        match result {
+            Ok(idle_time) => {
+                *failures = 0;
+                Some(idle_time)
+            }
+            Err(err) => {
+                *failures += 1;
+                if *failures < MAX_FAILURES {
+                    error!("Reading idle time from {source} failed: {err}");
+                } else if *failures == MAX_FAILURES {
+                    warn!(
+                        "Reading idle time from {source} keeps failing, using {} instead: {err}",
+                        source.fallback()
+                    );
+                }
+                None
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn foreground(&mut self) -> Option<&str> {
+        self.foreground
+            .get_or_insert_with(|| foreground_user().ok().flatten())
+            .as_deref()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn break_enforcer_idle(&mut self) -> Result<Duration, Error> {
+        let result = match &mut self.break_enforcer {
+            Ok(break_enforcer) => {
+                break_enforcer.idle_since().map_err(|err| err.to_string())
+            }
+            Err(err) => Err(format!("previous connection failed: {err}")),
+        };
+
+        result.map_err(|err| {
+            // Reconnect for the next try
+            self.break_enforcer = break_enforcer::Api::new();
+            Error::BreakEnforcer(err)
+        })
+    }
+
+    // No idle source on Windows yet, so always active
+    #[cfg(target_os = "windows")]
+    #[allow(clippy::unused_self)]
+    fn break_enforcer_idle(&mut self) -> Result<Duration, Error> {
+        Ok(Duration::default())
+    }
+}
+
+// The least idle of the user's sessions in the foreground, sessions in
+// the background (like after switching users) don't count as use
+#[cfg(target_os = "linux")]
+fn logind_idle(user: &str) -> Result<Duration, Error> {
+    let user_properties = loginctl_show("user", user, &["Sessions"])?;
+    let sessions = user_properties.get("Sessions").map_or("", String::as_str);
+
+    let mut least_idle = Duration::MAX;
+    for session in sessions.split_whitespace() {
+        let properties = loginctl_show(
+            "session",
+            session,
+            &["Active", "IdleHint", "IdleSinceHint"],
+        )?;
+        if property(&properties, "Active")? != "yes" {
+            continue;
+        }
+        least_idle = least_idle.min(session_idle(&properties)?);
+    }
+
+    Ok(least_idle)
+}
+
+#[cfg(target_os = "linux")]
+fn session_idle(
+    properties: &HashMap<String, String>,
+) -> Result<Duration, Error> {
+    if property(properties, "IdleHint")? != "yes" {
+        return Ok(Duration::default());
+    }
+
+    // Microseconds since the epoch
+    let since = property(properties, "IdleSinceHint")?;
+    let since = Duration::from_micros(
+        since
+            .parse()
+            .map_err(|_| Error::Parse(format!("IdleSinceHint={since}")))?,
+    );
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(now.saturating_sub(since))
+}
+
+/// Owner of the session in front of the screen, if any
+#[cfg(target_os = "linux")]
+fn foreground_user() -> Result<Option<String>, Error> {
+    let seat = loginctl_show("seat", "seat0", &["ActiveSession"])?;
+    let Some(session) = seat.get("ActiveSession").filter(|s| !s.is_empty())
+    else {
+        return Ok(None);
+    };
+
+    let session = loginctl_show("session", session, &["Name"])?;
+    Ok(session.get("Name").cloned())
+}
+
+// Properties of a logind user, session or seat, empty when a user is not
+// logged in
+#[cfg(target_os = "linux")]
+fn loginctl_show(
+    kind: &str,
+    id: &str,
+    properties: &[&str],
+) -> Result<HashMap<String, String>, Error> {
+    let mut command = Command::new("loginctl");
+    command.arg(format!("show-{kind}")).arg(id);
+    for property in properties {
+        command.arg(format!("--property={property}"));
+    }
+    let output = command.output()?;
+
+    let err = std::str::from_utf8(&output.stderr)?;
+    if err.contains("is not logged in or lingering") {
+        return Ok(HashMap::new());
+    }
+    if !output.status.success() {
+        return Err(Error::Loginctl(err.trim().to_owned()));
+    }
+
+    Ok(std::str::from_utf8(&output.stdout)?
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect())
+}
+
+#[cfg(target_os = "linux")]
+fn property<'a>(
+    properties: &'a HashMap<String, String>,
+    name: &str,
+) -> Result<&'a str, Error> {
+    properties
+        .get(name)
+        .map(String::as_str)
+        .ok_or_else(|| Error::Parse(format!("no {name}")))
+}
+
+#[cfg(target_os = "windows")]
+fn logind_idle(_user: &str) -> Result<Duration, Error> {
+    Ok(Duration::default())
+}
@@ -29,8 +29,6 @@ pub fn _unwrap_or_err_else<T, E: Debug, F: FnOnce(E) -> T>(
 ) -> T {
     match fallible {
         Ok(res) => res,
-        Err(err) => {
-            function(err)
-        }
+        Err(err) => function(err),
     }
 }
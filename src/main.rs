@@ -1,9 +1,23 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
 
+#[cfg(target_os = "linux")]
+mod audit;
 mod config;
+#[cfg(target_os = "linux")]
+mod control;
+mod duration;
+mod edit;
 mod file_io;
+mod firewall;
+mod history;
+mod idle;
 mod logging;
 mod notification;
+mod pam;
+mod pin;
+mod process;
 mod run;
 #[cfg(target_os = "windows")]
 mod session;
@@ -11,11 +25,16 @@ mod status;
 mod time_slot;
 mod tracker;
 mod user;
-
-const BREAK_IDLE_THRESHOLD: u64 = 10;
+mod weekday;
 
 #[derive(Parser)]
 struct Cli {
+    /// Directory with the config files
+    #[arg(long, global = true, env = "TIME_GUARDIAN_CONFIG_DIR")]
+    config_dir: Option<PathBuf>,
+    /// Directory for the status and other state files
+    #[arg(long, global = true, env = "TIME_GUARDIAN_STATE_DIR")]
+    state_dir: Option<PathBuf>,
     #[command(subcommand)]
     command: Command,
 }
@@ -23,20 +42,101 @@ struct Cli {
 #[derive(Subcommand)]
 enum Command {
     /// Monitor and enforce time
-    Run,
+    Run {
+        /// User to notify when running on a previous or fallback config
+        #[arg(long)]
+        admin: Option<String>,
+        /// What to enforce when no config file can be loaded at all
+        #[arg(long, value_enum, default_value_t)]
+        on_missing_config: config::MissingConfigPolicy,
+        /// Track and warn as usual, but never log anyone out
+        #[arg(long)]
+        dry_run: bool,
+    },
     /// Print machine-readable spent time in seconds
     Spent { user: String },
     /// Print human-readable time left message
     Status { user: String },
+    /// Print the time spent on past days
+    History {
+        user: String,
+        /// How many of the most recent days to show
+        #[arg(long, default_value_t = 7)]
+        days: usize,
+    },
+    /// Deny a login outside the allowed time, for use with pam_exec
+    PamCheck {
+        #[arg(env = "PAM_USER")]
+        user: String,
+    },
+    /// Validate a config file and print the effective config per user
+    CheckConfig { path: Option<String> },
+    /// Print the JSON Schema of the config file
+    Schema,
+    /// Edit the config in $EDITOR, only saving it once it is valid
+    EditConfig,
+    /// Write a first config for the users to guard
+    Init(config::InitArgs),
+    /// Change or show the config without editing it by hand
+    ///
+    /// Changes rewrite the whole file: durations like "1h30m" are written
+    /// as seconds and day keys like "Mo-Fr" as "Mo,Tu,We,Th,Fr".
+    Config {
+        #[command(subcommand)]
+        command: config::ConfigCommand,
+    },
+    /// Ask a parent for more time today
+    #[cfg(target_os = "linux")]
+    RequestTime {
+        minutes: u64,
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// See and answer requests for more time
+    #[cfg(target_os = "linux")]
+    Requests {
+        #[command(subcommand)]
+        command: control::RequestsCommand,
+    },
+    /// Pause enforcement for the user running this, needs the PIN
+    #[cfg(target_os = "linux")]
+    Override {
+        /// Minutes to pause for, at most the rest of the day which is
+        /// also the default
+        #[arg(long)]
+        minutes: Option<u64>,
+    },
 }
 
 fn main() {
     env_logger::init();
     let cli = Cli::parse();
+    file_io::path::init(cli.config_dir, cli.state_dir);
 
     match cli.command {
-        Command::Run => run::run(),
+        Command::Run {
+            admin,
+            on_missing_config,
+            dry_run,
+        } => run::run(admin.as_deref(), on_missing_config, dry_run),
         Command::Spent { user } => status::spent(&user),
         Command::Status { user } => status::status(&user),
+        Command::History { user, days } => history::print(&user, days),
+        Command::PamCheck { user } => pam::pam_check(&user),
+        Command::CheckConfig { path } => {
+            config::check(path.as_deref().unwrap_or(file_io::path::config()))
+        }
+        Command::Schema => config::print_schema(),
+        Command::EditConfig => edit::edit_config(),
+        Command::Init(args) => config::init(args),
+        Command::Config { command } => config::run_command(command),
+        #[cfg(target_os = "linux")]
+        Command::RequestTime { minutes, reason } => {
+            control::request_time(minutes, reason);
+        }
+        #[cfg(target_os = "linux")]
+        Command::Requests { command } => control::requests(command),
+        #[cfg(target_os = "linux")]
+        Command::Override { minutes } => control::pause(minutes),
     }
 }
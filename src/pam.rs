@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use crate::config::{Config, Enforcement};
+use crate::status;
+use crate::tracker::{Tracker, UserCounter};
+
+/// For `pam_exec`: exits with 1 and prints why when the login should be
+/// denied, so users don't get logged in only to be kicked out again
+pub(crate) fn pam_check(user: &str) {
+    if let Some(reason) = login_denied(user) {
+        println!("{reason}");
+        std::process::exit(1);
+    }
+}
+
+// Same checks as the `run` loop, before any time is spent
+fn login_denied(user: &str) -> Option<String> {
+    let config = Config::in_effect();
+    let user_config = config.get(user)?;
+    // Without logging out, the session may stay and only apps get closed
+    if !user_config.enforce || user_config.on_time_up != Enforcement::Logout {
+        return None;
+    }
+
+    // No tracker for today means nothing was spent yet
+    let tracker = Tracker::load()
+        .ok()
+        .filter(|tracker| !tracker.is_outdated());
+    if tracker
+        .as_ref()
+        .and_then(|tracker| tracker.counter.get(user))
+        .and_then(UserCounter::pause_end)
+        .is_some()
+    {
+        // A parent used the override PIN
+        return None;
+    }
+
+    if !user_config.now_within_timeslot() {
+        let slots: Vec<String> = user_config
+            .timeslots_today()
+            .unwrap_or_default()
+            .iter()
+            .map(|slot| {
+                format!(
+                    "{}-{}",
+                    slot.start.strftime("%H:%M"),
+                    slot.end.strftime("%H:%M")
+                )
+            })
+            .collect();
+        return Some(format!(
+            "You can only log in between {} today",
+            slots.join(", ")
+        ));
+    }
+
+    if let Some(until) = tracker
+        .as_ref()
+        .and_then(|tracker| tracker.counter.get(user))
+        .and_then(UserCounter::cooldown_end)
+    {
+        return Some(format!(
+            "You were logged out recently, you can log back in at {}",
+            status::format_time(until)
+        ));
+    }
+    let counter = tracker
+        .as_ref()
+        .and_then(|tracker| tracker.counter.get(user));
+    let spent = counter.map_or(Duration::ZERO, |counter| counter.total_spent);
+    let allowed = counter
+        .map_or(user_config.total_allowed_today(), |counter| {
+            counter.allowed(user_config)
+        });
+    if spent >= allowed {
+        return Some(format!(
+            "Your {} of screen time for today is used up",
+            status::format(allowed)
+        ));
+    }
+    if tracker.is_some_and(|tracker| {
+        tracker.counter.contains_key(user)
+            && tracker.timeslot_over_time(&config, user)
+    }) {
+        return Some("The time for this time slot is used up".to_owned());
+    }
+
+    None
+}
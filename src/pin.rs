@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+#[cfg(target_os = "linux")]
+use argon2::PasswordVerifier;
+use argon2::{Argon2, PasswordHasher};
+use color_eyre::{eyre::eyre, Result};
+use password_hash::{rand_core::OsRng, PasswordHash, SaltString};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+
+use crate::file_io;
+
+/// Argon2 hash of the PIN parents use to override, in PHC format
+/// (`$argon2id$...`)
+#[derive(Debug, Clone, PartialEq, SerializeDisplay, DeserializeFromStr)]
+pub struct PinHash(String);
+
+impl PinHash {
+    pub(crate) fn new(pin: &str) -> Result<Self> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(pin.as_bytes(), &salt)
+            .map_err(|err| eyre!("Couldn't hash PIN: {err}"))?;
+        Ok(Self(hash.to_string()))
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(crate) fn verify(&self, pin: &str) -> bool {
+        let hash = PasswordHash::new(&self.0)
+            .expect("Checked when the PINs were loaded");
+        Argon2::default()
+            .verify_password(pin.as_bytes(), &hash)
+            .is_ok()
+    }
+}
+
+impl FromStr for PinHash {
+    type Err = password_hash::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        PasswordHash::new(s)?;
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl fmt::Display for PinHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Per user, in a file only root can read. Guarded users could brute force
+// a short PIN offline if they got hold of the hash.
+type Pins = BTreeMap<String, PinHash>;
+
+fn load_all() -> Result<Pins> {
+    let path = file_io::path::override_pins();
+    if !Path::new(path).exists() {
+        return Ok(Pins::new());
+    }
+    file_io::load(path)
+}
+
+pub(crate) fn store(user: &str, hash: PinHash) -> Result<()> {
+    let mut pins = load_all()?;
+    pins.insert(user.to_owned(), hash);
+    file_io::store_private(&pins, file_io::path::override_pins())?;
+    Ok(())
+}
+
+/// The PIN hash of `user`, if a parent set one
+#[cfg(target_os = "linux")]
+pub(crate) fn get(user: &str) -> Result<Option<PinHash>> {
+    Ok(load_all()?.remove(user))
+}
+
+/// Read a PIN from the terminal without showing it
+pub(crate) fn read(prompt: &str) -> Result<String> {
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+
+    let echo = set_echo(false);
+    let mut pin = String::new();
+    let read = io::stdin().lock().read_line(&mut pin);
+    if echo {
+        set_echo(true);
+        eprintln!();
+    }
+    read?;
+
+    let pin = pin.trim_end_matches(['\r', '\n']).to_owned();
+    if pin.is_empty() {
+        return Err(eyre!("No PIN given"));
+    }
+    Ok(pin)
+}
+
+// Returns whether stdin is a terminal we changed
+#[cfg(target_os = "linux")]
+fn set_echo(on: bool) -> bool {
+    // Safe: termios is only used after tcgetattr filled it in
+    unsafe {
+        let mut termios = std::mem::zeroed::<libc::termios>();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+            return false;
+        }
+        if on {
+            termios.c_lflag |= libc::ECHO;
+        } else {
+            termios.c_lflag &= !libc::ECHO;
+        }
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) == 0
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn set_echo(_on: bool) -> bool {
+    false
+}
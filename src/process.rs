@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::MetadataExt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+#[allow(unused_imports)]
+use log::{error, info};
+use regex::Regex;
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+
+#[cfg(target_os = "linux")]
+use crate::user;
+
+/// How long a process gets to quit after SIGTERM, before SIGKILL
+pub(crate) const DEFAULT_GRACE: Duration = Duration::from_secs(10);
+
+// Executables of the desktop session itself, by file name
+const BACKGROUND: &[&str] = &[
+    r"systemd|\(sd-pam\)|dbus-daemon|dbus-broker(-launch)?",
+    r"pipewire(-pulse)?|wireplumber|pulseaudio",
+    r"(ba|z|da|fi)?sh|login|sshd|ssh-agent|gpg-agent|tmux: server",
+    r"Xorg|Xwayland|at-spi-bus-launcher|at-spi2-registryd|dconf-service",
+    r"xdg-[a-z-]+|ibus-[a-z-]+|polkit-[a-z-]+|gdm-[a-z-]+",
+    r"gnome-shell|gnome-session-binary|gnome-keyring-daemon|gsd-[a-z-]+",
+    r"gvfsd?(-[a-z-]+)?|goa-daemon|goa-identity-service|evolution-[a-z-]+",
+    r"tracker-miner-[a-z0-9-]+|localsearch-[0-9]+",
+    r"plasmashell|kwin_(wayland|x11)|ksmserver|kded[56]?|kactivitymanagerd",
+    r"xfce4-[a-z-]+|xfwm4|xfdesktop|lxsession",
+];
+
+/// The default `background` processes
+pub(crate) fn default_background() -> Vec<Pattern> {
+    BACKGROUND
+        .iter()
+        .map(|names| {
+            Pattern(
+                Regex::new(&format!("(^|/)({names})$"))
+                    .expect("Built in patterns are valid"),
+            )
+        })
+        .collect()
+}
+
+/// A regex matched against the executable path and the command line
+#[derive(Debug, Clone, SerializeDisplay, DeserializeFromStr)]
+pub struct Pattern(Regex);
+
+impl FromStr for Pattern {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self(Regex::new(s)?))
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.as_str())
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Process {
+    pub(crate) pid: i32,
+    exe: String,
+    cmdline: String,
+}
+
+impl Process {
+    pub(crate) fn matches(&self, patterns: &[Pattern]) -> bool {
+        patterns.iter().any(|Pattern(regex)| {
+            regex.is_match(&self.exe) || regex.is_match(&self.cmdline)
+        })
+    }
+}
+
+impl fmt::Display for Process {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.pid, self.cmdline)
+    }
+}
+
+/// All processes owned by `user`, from /proc
+#[cfg(target_os = "linux")]
+pub(crate) fn of_user(user: &str) -> Result<Vec<Process>> {
+    let uid = user::uid(user)?;
+
+    let mut processes = Vec::new();
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok())
+        else {
+            continue;
+        };
+        // Processes can exit while we look at them
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.uid() != uid {
+            continue;
+        }
+
+        let exe = fs::read_link(entry.path().join("exe"))
+            .map(|exe| exe.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let cmdline = fs::read(entry.path().join("cmdline"))
+            .map(|cmdline| {
+                String::from_utf8_lossy(&cmdline)
+                    .trim_end_matches('\0')
+                    .replace('\0', " ")
+            })
+            .unwrap_or_default();
+        // Zombies, about to be gone
+        if exe.is_empty() && cmdline.is_empty() {
+            continue;
+        }
+        processes.push(Process { pid, exe, cmdline });
+    }
+
+    Ok(processes)
+}
+
+// Not supported on Windows yet
+#[cfg(target_os = "windows")]
+pub(crate) fn of_user(_user: &str) -> Result<Vec<Process>> {
+    Ok(Vec::new())
+}
+
+/// Ends processes with SIGTERM, then SIGKILL if they are still running
+/// after the grace period
+#[derive(Default)]
+pub(crate) struct Terminator(HashMap<i32, Instant>);
+
+impl Terminator {
+    /// Returns whether the process was just asked to quit
+    pub(crate) fn end(&mut self, process: &Process, grace: Duration) -> bool {
+        match self.0.get(&process.pid) {
+            None => {
+                info!("Terminating {process}");
+                signal(process, Signal::Term);
+                self.0.insert(process.pid, Instant::now());
+                true
+            }
+            Some(since) if since.elapsed() >= grace => {
+                info!("Killing {process}, still running after {grace:?}");
+                signal(process, Signal::Kill);
+                false
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// Forget processes that are gone, their pid can be reused
+    pub(crate) fn forget_exited(&mut self, running: &[Process]) {
+        self.0
+            .retain(|pid, _| running.iter().any(|process| process.pid == *pid));
+    }
+}
+
+enum Signal {
+    Term,
+    Kill,
+}
+
+#[cfg(target_os = "linux")]
+fn signal(process: &Process, signal: Signal) {
+    let signal = match signal {
+        Signal::Term => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+    };
+    // Safe: kill only sends a signal
+    if unsafe { libc::kill(process.pid, signal) } != 0 {
+        error!(
+            "Couldn't signal {process}: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn signal(process: &Process, _signal: Signal) {
+    error!("Can't end {process}, not supported on Windows");
+}
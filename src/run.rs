@@ -1,3 +1,8 @@
+use std::collections::{HashMap, HashSet};
+#[cfg(target_os = "linux")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(target_os = "linux")]
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use std::time::Instant;
@@ -5,53 +10,173 @@ use std::time::Instant;
 #[allow(unused_imports)]
 use log::{error, info, trace};
 
+use crate::config::BreakRule;
 use crate::config::Config;
+use crate::config::ConfigState;
+use crate::config::Enforcement;
+use crate::config::MissingConfigPolicy;
 use crate::config::UserConfig;
+#[cfg(target_os = "linux")]
+use crate::control::ControlSocket;
 use crate::file_io::path;
+use crate::firewall::Firewall;
+use crate::history::History;
+use crate::idle::IdleMonitor;
 use crate::notification;
+use crate::process::{self, Pattern, Process, Terminator};
+use crate::status;
 use crate::tracker::Tracker;
 use crate::user;
-use crate::BREAK_IDLE_THRESHOLD;
 
-pub(crate) fn run() {
-    let mut full_config = Config::initialize_from_files();
+pub(crate) fn run(
+    admin: Option<&str>,
+    policy: MissingConfigPolicy,
+    dry_run: bool,
+) {
+    let (mut full_config, mut config_state) =
+        Config::initialize_from_files(policy, admin);
     let mut tracker = Tracker::initialize(&full_config);
-    full_config.store(path::RAMPEDUP);
+    full_config.store(path::rampedup());
+    report_config_state(&config_state, admin);
 
-    #[cfg(target_os = "linux")]
-    let mut break_enforcer = break_enforcer::Api::new();
-    #[cfg(target_os = "linux")]
-    let mut retries = 0;
+    let mut idle = IdleMonitor::new();
     #[cfg(target_os = "windows")]
     let mut notified_startup = false;
 
+    // Over time but not logged out, so that is only logged once a day
+    let mut not_logged_out = HashSet::new();
+    // Last reminder for monitor only users that are over time
+    let mut last_nag: HashMap<String, Instant> = HashMap::new();
+    // Apps being ended per user, to kill them if they don't quit
+    let mut terminators: HashMap<String, Terminator> = HashMap::new();
+
+    #[cfg(target_os = "linux")]
+    let mut control = ControlSocket::bind();
+    let mut firewall = Firewall::default();
+    // Rules left behind when we didn't shut down cleanly
+    firewall.clear();
+    // So network rules get removed when the service stops
+    #[cfg(target_os = "linux")]
+    let shutdown = Arc::new(AtomicBool::new(false));
+    #[cfg(target_os = "linux")]
+    for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT] {
+        if let Err(err) =
+            signal_hook::flag::register(signal, Arc::clone(&shutdown))
+        {
+            error!("Couldn't handle signal {signal}: {err}");
+        }
+    }
+
     let mut now = Instant::now();
 
     loop {
+        #[cfg(target_os = "linux")]
+        if shutdown.load(Ordering::Relaxed) {
+            info!("Shutting down");
+            firewall.clear();
+            return;
+        }
+
         if tracker.is_outdated() {
             info!("New day, resetting");
-            tracker = Tracker::new(&full_config);
+            History::record(&tracker);
+            // Reload first, so users new in the config get a counter too
+            full_config = full_config.reload(&mut config_state);
+            full_config.store(path::rampedup());
+            report_config_state(&config_state, admin);
 
-            full_config = full_config.reload();
-            full_config.store(path::RAMPEDUP);
+            tracker = tracker.next_day(&full_config);
+            not_logged_out.clear();
+            last_nag.clear();
+            #[cfg(target_os = "linux")]
+            control.new_day();
+        } else if Config::reload_requested() {
+            // TODO? limitation: new timeslots only get a counter on new day
+            info!("Config reload requested");
+            full_config = full_config.reload(&mut config_state);
+            full_config.store(path::rampedup());
+            tracker.add_new_users(&full_config);
+            report_config_state(&config_state, admin);
         }
 
+        #[cfg(target_os = "linux")]
+        control.handle(&mut tracker, &full_config, admin);
+        firewall.keep_only(&full_config);
+
         thread::sleep(Duration::from_secs(1));
         let elapsed = now.elapsed();
         now = Instant::now();
 
+        idle.next_tick();
         for (user, user_config) in full_config.iter() {
-            // Default to 0 idle = active
-            #[cfg(target_os = "linux")]
-            let idle_time = get_idle_time(&mut break_enforcer, &mut retries);
-            #[cfg(target_os = "windows")]
-            let idle_time = Duration::default();
+            let active = user::is_active(user);
+
+            let paused = tracker.counter[user].pause_end().is_some();
+            let enforcing = !dry_run && user_config.enforce && !paused;
+            if let Some(until) = tracker.counter[user].cooldown_end() {
+                if enforcing && active {
+                    notification::notify_user(
+                        user,
+                        &format!(
+                            "You can log back in at {}",
+                            status::format_time(until)
+                        ),
+                    );
+                    user::logout(user);
+                    continue;
+                }
+            }
+
+            // Only users that are logged in have an idle time to read
+            let in_use = active
+                && idle.idle_time(user, user_config.idle_source)
+                    < user_config.idle_threshold;
+            if let Some(rule) = &user_config.breaks {
+                check_break(
+                    &mut tracker,
+                    rule,
+                    user,
+                    in_use,
+                    elapsed,
+                    enforcing,
+                );
+            }
+
+            let processes = list_processes(user_config, user);
+            let terminator = terminators.entry(user.clone()).or_default();
+            terminator.forget_exited(&processes);
+            if !user_config.categories.is_empty() {
+                track_categories(
+                    &mut tracker,
+                    terminator,
+                    user_config,
+                    user,
+                    &processes,
+                    in_use,
+                    elapsed,
+                    enforcing,
+                );
+            }
 
-            if user::is_active(user)
-                && idle_time < Duration::from_secs(BREAK_IDLE_THRESHOLD)
-            {
+            // Also while not in use, so it is lifted right after a new day
+            // or config change
+            let block = enforcing
+                && user_config.on_time_up == Enforcement::BlockNetwork
+                && is_over_time(&tracker, &full_config, user_config, user);
+            if firewall.set_blocked(user, block) && block {
+                notification::notify_user(
+                    user,
+                    "Your screen time for today is up, the internet is blocked",
+                );
+            }
+
+            if in_use {
                 // TODO? limitation: only reloads new timeslot settings on new day
-                tracker.add(user, elapsed);
+                if is_exempt(user_config, &processes) {
+                    tracker.add_exempt(user, elapsed);
+                } else {
+                    tracker.add(user, elapsed);
+                }
 
                 #[cfg(target_os = "windows")]
                 if !notified_startup {
@@ -59,7 +184,7 @@ pub(crate) fn run() {
                         user,
                         &format!(
                             "You have {:.0?} left today",
-                            user_config.total_allowed_today()
+                            tracker.counter[user].allowed(user_config)
                                 - tracker.counter[user].total_spent
                         ),
                     );
@@ -69,19 +194,42 @@ pub(crate) fn run() {
                 trace!(
                     "{user} spent {:.1?} out of {:?}",
                     tracker.counter[user].total_spent,
-                    user_config.total_allowed_today()
+                    tracker.counter[user].allowed(user_config)
                 );
                 trace!("Timeslots: {:#?}", tracker.counter[user].time_slots);
 
-                // TODO: make fn on Tracker
-                if tracker.counter[user].total_spent
-                    >= user_config.total_allowed_today()
-                    || tracker.timeslot_over_time(&full_config, user)
-                    || !user_config.now_within_timeslot()
-                {
-                    user::logout(user);
-                    // This user doesn't need to be accounted for right now
-                    continue;
+                if is_over_time(&tracker, &full_config, user_config, user) {
+                    if !enforcing {
+                        if not_logged_out.insert(user.clone()) {
+                            let reason = if dry_run {
+                                "dry run"
+                            } else if paused {
+                                "paused with the override PIN"
+                            } else {
+                                "monitor only"
+                            };
+                            info!("Not logging out {user}, {reason}");
+                        }
+                        if !user_config.enforce {
+                            nag(&mut last_nag, user_config, user);
+                        }
+                    } else {
+                        match &user_config.on_time_up {
+                            Enforcement::Logout => {
+                                force_logout(&mut tracker, user_config, user);
+                                // This user doesn't need to be accounted for right now
+                                continue;
+                            }
+                            Enforcement::Terminate { patterns, grace } => {
+                                end_apps(
+                                    terminator, user, &processes, patterns,
+                                    *grace,
+                                );
+                            }
+                            // Blocked above
+                            Enforcement::BlockNetwork => (),
+                        }
+                    }
                 }
 
                 tracker.store();
@@ -92,32 +240,218 @@ pub(crate) fn run() {
     }
 }
 
-#[cfg(target_os = "linux")]
-pub(crate) fn get_idle_time(
-    api_connection: &mut Result<break_enforcer::Api, break_enforcer::Error>,
-    retries: &mut usize,
-) -> Duration {
-    match api_connection {
-        Ok(ref mut break_enforcer) => match break_enforcer.idle_since() {
-            Ok(time) => time,
-            Err(err) => {
-                if *retries < 3 {
-                    error!("Idle time reading failed: {err}");
-                    *retries += 1;
-                }
-                *api_connection = break_enforcer::Api::new();
-                Duration::default()
-            }
-        },
+// TODO: make fn on Tracker
+fn is_over_time(
+    tracker: &Tracker,
+    full_config: &Config,
+    config: &UserConfig,
+    user: &str,
+) -> bool {
+    let counter = &tracker.counter[user];
+    counter.total_spent >= counter.allowed(config)
+        || tracker.timeslot_over_time(full_config, user)
+        || !config.now_within_timeslot()
+}
+
+// Counts continuous use, warns before a break and locks during it
+fn check_break(
+    tracker: &mut Tracker,
+    rule: &BreakRule,
+    user: &str,
+    in_use: bool,
+    elapsed: Duration,
+    enforcing: bool,
+) {
+    let counter = tracker
+        .counter
+        .get_mut(user)
+        .expect("Should have added any new users on load");
+
+    if counter.break_end().is_some() {
+        // Back at it during the break
+        if in_use && enforcing {
+            user::lock(user);
+        }
+        return;
+    }
+    if !in_use {
+        counter.rest(elapsed, rule.length);
+        return;
+    }
+
+    counter.add_to_streak(elapsed);
+    let left = rule.after.saturating_sub(counter.streak);
+    if left.is_zero() {
+        let until = counter.start_break(rule.length);
+        tracker.store();
+        notification::notify_user(
+            user,
+            &format!(
+                "Time for a break, you can continue at {}",
+                status::format_time(until)
+            ),
+        );
+        if enforcing {
+            user::lock(user);
+        }
+    } else if left.as_secs() == rule.warn_before.as_secs() {
+        notification::notify_user(
+            user,
+            &format!("Break in {}", status::format(left)),
+        );
+    }
+}
+
+// Only when some app config needs them, /proc is big
+fn list_processes(config: &UserConfig, user: &str) -> Vec<Process> {
+    if config.categories.is_empty()
+        && config.allow_list.is_empty()
+        && config.on_time_up == Enforcement::Logout
+    {
+        return Vec::new();
+    }
+
+    match process::of_user(user) {
+        Ok(processes) => processes,
         Err(err) => {
-            if *retries < 3 {
-                error!("Previous break enforcer connection failed: {err}");
-                *retries += 1;
-            }
-            *api_connection = break_enforcer::Api::new();
-            Duration::default()
+            error!("Couldn't list processes of {user}: {err:?}");
+            Vec::new()
+        }
+    }
+}
+
+// Allow-listed apps run and nothing else does, apart from the session
+fn is_exempt(config: &UserConfig, processes: &[Process]) -> bool {
+    let mut apps = processes
+        .iter()
+        .filter(|process| !process.matches(&config.background))
+        .peekable();
+
+    !config.allow_list.is_empty()
+        && apps.peek().is_some()
+        && apps.all(|process| {
+            process.matches(&config.allow_list)
+                && !config
+                    .categories
+                    .values()
+                    .any(|category| process.matches(&category.patterns))
+        })
+}
+
+// Counts time in app categories while in use, and ends apps over budget
+#[allow(clippy::too_many_arguments)]
+fn track_categories(
+    tracker: &mut Tracker,
+    terminator: &mut Terminator,
+    config: &UserConfig,
+    user: &str,
+    processes: &[Process],
+    in_use: bool,
+    elapsed: Duration,
+    enforcing: bool,
+) {
+    let counter = tracker
+        .counter
+        .get_mut(user)
+        .expect("Should have added any new users on load");
+
+    for (name, category) in &config.categories {
+        let matched: Vec<_> = processes
+            .iter()
+            .filter(|process| process.matches(&category.patterns))
+            .collect();
+        if matched.is_empty() {
+            continue;
+        }
+
+        if in_use {
+            counter.add_to_category(name, elapsed);
+        }
+        // Nothing counted yet when not in use since the day started
+        let spent = counter.categories.get(name).copied().unwrap_or_default();
+        if spent < category.allowed || !enforcing {
+            continue;
+        }
+
+        let mut ended = false;
+        for process in matched {
+            ended |= terminator.end(process, process::DEFAULT_GRACE);
+        }
+        if ended {
+            notification::notify_user(
+                user,
+                &format!("Your time for {name} is up for today"),
+            );
+        }
+    }
+}
+
+// Ends the configured apps instead of the whole session
+fn end_apps(
+    terminator: &mut Terminator,
+    user: &str,
+    processes: &[Process],
+    patterns: &[Pattern],
+    grace: Duration,
+) {
+    let mut ended = false;
+    for process in processes.iter().filter(|p| p.matches(patterns)) {
+        ended |= terminator.end(process, grace);
+    }
+    if ended {
+        notification::notify_user(
+            user,
+            "Your screen time for today is up, closing your apps",
+        );
+    }
+}
+
+// With a cooldown set, the user is told when they can log back in
+fn force_logout(tracker: &mut Tracker, config: &UserConfig, user: &str) {
+    if let Some(cooldown) = config.relogin_cooldown {
+        tracker.start_cooldown(user, cooldown);
+        tracker.store();
+        if let Some(until) = tracker.counter[user].cooldown_end() {
+            notification::notify_user(
+                user,
+                &format!(
+                    "Logging you out, you can log back in at {}",
+                    status::format_time(until)
+                ),
+            );
         }
     }
+
+    user::logout(user);
+}
+
+// Once when going over time, then every `nag_every` if set
+fn nag(
+    last_nag: &mut HashMap<String, Instant>,
+    config: &UserConfig,
+    user: &str,
+) {
+    let due = match (last_nag.get(user), config.nag_every) {
+        (None, _) => true,
+        (Some(last), Some(every)) => last.elapsed() >= every,
+        (Some(_), None) => false,
+    };
+
+    if due {
+        notification::notify_user(
+            user,
+            "You are over your screen time for today, time for a break!",
+        );
+        last_nag.insert(user.to_owned(), Instant::now());
+    }
+}
+
+fn report_config_state(config_state: &ConfigState, admin: Option<&str>) {
+    config_state.store();
+
+    if let (Some(problem), Some(admin)) = (config_state.describe(), admin) {
+        notification::notify_user(admin, &problem);
+    }
 }
 
 pub(crate) fn issue_warnings(
@@ -128,16 +462,27 @@ pub(crate) fn issue_warnings(
     // TODO: make short and long warnings different
     // (and multiple possible)
 
-    let time_left = config
-        .total_allowed_today()
-        .saturating_sub(tracker.counter[user].total_spent);
+    let counter = &tracker.counter[user];
+    let time_left = counter.allowed(config).saturating_sub(counter.total_spent);
 
     if time_left.as_secs() == config.short_warning.as_secs()
         || time_left.as_secs() == config.long_warning.as_secs()
     {
-        notification::notify_user(
-            user,
-            &format!("You will be logged out in {time_left:.0?} seconds!",),
-        );
+        let text = if !config.enforce {
+            format!("Your screen time for today is up in {time_left:.0?}")
+        } else {
+            match config.on_time_up {
+                Enforcement::Logout => format!(
+                    "You will be logged out in {time_left:.0?} seconds!"
+                ),
+                Enforcement::Terminate { .. } => {
+                    format!("Your apps will be closed in {time_left:.0?}")
+                }
+                Enforcement::BlockNetwork => {
+                    format!("The internet will be blocked in {time_left:.0?}")
+                }
+            }
+        };
+        notification::notify_user(user, &text);
     }
 }
@@ -1,6 +1,11 @@
 use std::time::Duration;
 
-use crate::{config::Config, file_io, tracker::Tracker};
+use jiff::{tz::TimeZone, Timestamp};
+
+use crate::{
+    config::{Config, ConfigState},
+    tracker::Tracker,
+};
 
 pub(crate) fn spent(user: &str) {
     let spent = get_spent(user).as_secs_f64();
@@ -25,15 +30,34 @@ fn get_spent(user: &str) -> Duration {
     }
 }
 
+// Extra time approved by a parent today
+fn get_granted(user: &str) -> Duration {
+    Tracker::load()
+        .ok()
+        .filter(|tracker| !tracker.is_outdated())
+        .and_then(|tracker| Some(tracker.counter.get(user)?.granted))
+        .unwrap_or_default()
+}
+
 pub(crate) fn status(user: &str) {
     let spent = get_spent(user);
-    let config = Config::initialize_from_files();
-    let allowed = config.allowed(user);
+    let config = Config::in_effect();
 
-    println!("time left: {}", format(allowed.saturating_sub(spent)));
+    match config.allowed(user) {
+        Some(allowed) => {
+            let allowed = allowed + get_granted(user);
+            println!("time left: {}", format(allowed.saturating_sub(spent)));
+        }
+        None => println!("{user} is not guarded"),
+    }
+
+    // Report what the daemon is running on, not what we just loaded
+    if let Some(problem) = ConfigState::load().ok().and_then(|s| s.describe()) {
+        println!("{problem}");
+    }
 }
 
-fn format(duration: Duration) -> String {
+pub(crate) fn format(duration: Duration) -> String {
     let seconds = duration.as_secs();
     let hours = seconds / 3600;
 
@@ -42,3 +66,11 @@ fn format(duration: Duration) -> String {
 
     format!("{hours:02}:{minutes:02}:{seconds:02}")
 }
+
+// Local time of day like 21:15
+pub(crate) fn format_time(timestamp: Timestamp) -> String {
+    timestamp
+        .to_zoned(TimeZone::system())
+        .strftime("%H:%M")
+        .to_string()
+}
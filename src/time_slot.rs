@@ -1,15 +1,20 @@
 use std::time::Duration;
 
 use jiff::{civil::Time, Zoned};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_with::{serde_as, DurationSecondsWithFrac};
+use serde_with::serde_as;
+
+use crate::duration::HumanDuration;
 
 #[serde_as]
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct TimeSlot {
+    #[schemars(with = "String")]
     pub(crate) start: Time,
+    #[schemars(with = "String")]
     pub(crate) end: Time,
-    #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
+    #[serde_as(as = "Option<HumanDuration>")]
     pub(crate) time: Option<Duration>,
 }
 
@@ -19,11 +24,7 @@ impl Default for TimeSlot {
         let end = Time::MAX;
         let time = Some(Duration::from_secs(86400));
 
-        Self {
-            start,
-            end,
-            time,
-        }
+        Self { start, end, time }
     }
 }
 
@@ -53,4 +54,3 @@ impl TimeSlot {
         self
     }
 }
-
@@ -1,16 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::time::Duration;
 
 use color_eyre::Result;
 use jiff::civil::Date;
-use jiff::Zoned;
+use jiff::{Timestamp, Zoned};
 use log::error;
 use serde_derive::{Deserialize, Serialize};
 use serde_with::{serde_as, DurationSecondsWithFrac};
 
 use crate::config::{Config, UserConfig};
 use crate::file_io;
+use crate::history::History;
 use crate::logging::log_error;
 use crate::time_slot::TimeSlot;
 
@@ -26,6 +27,34 @@ pub struct UserCounter {
     #[serde_as(as = "DurationSecondsWithFrac<f64>")]
     pub(crate) total_spent: Duration,
     pub(crate) time_slots: Option<Vec<TimeSlot>>,
+    // Set on a forced logout, no logging back in before then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) cooldown_until: Option<Timestamp>,
+    // Continuous use since the last break
+    #[serde(default)]
+    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    pub(crate) streak: Duration,
+    // Time not used since the streak was last added to
+    #[serde(default)]
+    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    resting: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) break_until: Option<Timestamp>,
+    // Time spent in each app category
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[serde_as(as = "BTreeMap<_, DurationSecondsWithFrac<f64>>")]
+    pub(crate) categories: BTreeMap<String, Duration>,
+    // Time with only allow-listed apps, not part of `total_spent`
+    #[serde(default)]
+    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    pub(crate) exempt: Duration,
+    // Extra time for today approved by a parent
+    #[serde(default, skip_serializing_if = "Duration::is_zero")]
+    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    pub(crate) granted: Duration,
+    // Enforcement paused by a parent with the override PIN
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) paused_until: Option<Timestamp>,
 }
 
 impl UserCounter {
@@ -37,9 +66,67 @@ impl UserCounter {
         Self {
             total_spent: Duration::default(),
             time_slots,
+            cooldown_until: None,
+            streak: Duration::default(),
+            resting: Duration::default(),
+            break_until: None,
+            categories: BTreeMap::new(),
+            exempt: Duration::default(),
+            granted: Duration::default(),
+            paused_until: None,
         }
     }
 
+    /// Allowed time today including any granted extra time
+    pub(crate) fn allowed(&self, config: &UserConfig) -> Duration {
+        config.total_allowed_today().saturating_add(self.granted)
+    }
+
+    pub(crate) fn add_to_category(
+        &mut self,
+        category: &str,
+        duration: Duration,
+    ) {
+        *self.categories.entry(category.to_owned()).or_default() += duration;
+    }
+
+    pub(crate) fn add_to_streak(&mut self, duration: Duration) {
+        self.streak += duration;
+        self.resting = Duration::default();
+    }
+
+    /// Not being used for as long as a break counts as one
+    pub(crate) fn rest(&mut self, duration: Duration, break_length: Duration) {
+        self.resting += duration;
+        if self.resting >= break_length {
+            self.streak = Duration::default();
+        }
+    }
+
+    pub(crate) fn start_break(&mut self, length: Duration) -> Timestamp {
+        let until = from_now(length);
+        self.streak = Duration::default();
+        self.resting = Duration::default();
+        self.break_until = Some(until);
+        until
+    }
+
+    /// The end of the current mandatory break, if any
+    pub(crate) fn break_end(&self) -> Option<Timestamp> {
+        self.break_until.filter(|until| *until > Timestamp::now())
+    }
+
+    /// The end of the cooldown after a forced logout, if still running
+    pub(crate) fn cooldown_end(&self) -> Option<Timestamp> {
+        self.cooldown_until
+            .filter(|until| *until > Timestamp::now())
+    }
+
+    /// The end of an override, if still running
+    pub(crate) fn pause_end(&self) -> Option<Timestamp> {
+        self.paused_until.filter(|until| *until > Timestamp::now())
+    }
+
     pub fn add_to_total_spent(&mut self, duration: Duration) {
         self.total_spent += duration;
     }
@@ -64,15 +151,10 @@ impl Tracker {
         let tracker = match Tracker::load() {
             Ok(mut tracker) => {
                 if tracker.is_outdated() {
-                    Tracker::new(config)
+                    History::record(&tracker);
+                    tracker.next_day(config)
                 } else {
-                    // Make sure we get any new users in the config
-                    let new_tracker = Tracker::new(config);
-                    for (new_user, new_counter) in new_tracker.counter {
-                        if !tracker.counter.contains_key(&new_user) {
-                            tracker.counter.insert(new_user, new_counter);
-                        }
-                    }
+                    tracker.add_new_users(config);
                     tracker
                 }
             }
@@ -100,12 +182,34 @@ impl Tracker {
         }
     }
 
+    /// A fresh tracker, only keeping cooldowns, breaks and overrides that
+    /// run past midnight
+    pub(crate) fn next_day(&self, config: &Config) -> Self {
+        let mut tracker = Tracker::new(config);
+        for (user, counter) in &mut tracker.counter {
+            let Some(old_counter) = self.counter.get(user) else {
+                continue;
+            };
+            counter.cooldown_until = old_counter.cooldown_end();
+            counter.break_until = old_counter.break_end();
+        }
+        tracker
+    }
+
+    /// Make sure we get any new users in the config
+    pub(crate) fn add_new_users(&mut self, config: &Config) {
+        let new_tracker = Tracker::new(config);
+        for (new_user, new_counter) in new_tracker.counter {
+            self.counter.entry(new_user).or_insert(new_counter);
+        }
+    }
+
     pub(crate) fn is_outdated(&self) -> bool {
         Zoned::now().datetime().date() != self.date
     }
 
     pub(crate) fn load() -> Result<Self> {
-        let file_content = fs::read_to_string(file_io::path::STATUS)?;
+        let file_content = fs::read_to_string(file_io::path::status())?;
         let tracker: Result<Tracker, _> = file_io::from_str(&file_content);
 
         tracker
@@ -113,7 +217,7 @@ impl Tracker {
 
     pub(crate) fn store(&self) {
         log_error(
-            file_io::store(&self, file_io::path::STATUS),
+            file_io::store(&self, file_io::path::status()),
             "Error while trying to store tracker",
         );
     }
@@ -128,6 +232,44 @@ impl Tracker {
         user_counter.add_to_current_timeslots(duration);
     }
 
+    pub(crate) fn add_exempt(&mut self, user: &str, duration: Duration) {
+        let user_counter = self
+            .counter
+            .get_mut(user)
+            .expect("Should have added any new users on load");
+
+        user_counter.exempt += duration;
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(crate) fn grant(&mut self, user: &str, extra: Duration) {
+        let user_counter = self
+            .counter
+            .get_mut(user)
+            .expect("Should have added any new users on load");
+
+        user_counter.granted = user_counter.granted.saturating_add(extra);
+    }
+
+    #[cfg(target_os = "linux")]
+    pub(crate) fn pause(&mut self, user: &str, until: Timestamp) {
+        let user_counter = self
+            .counter
+            .get_mut(user)
+            .expect("Should have added any new users on load");
+
+        user_counter.paused_until = Some(until);
+    }
+
+    pub(crate) fn start_cooldown(&mut self, user: &str, cooldown: Duration) {
+        let user_counter = self
+            .counter
+            .get_mut(user)
+            .expect("Should have added any new users on load");
+
+        user_counter.cooldown_until = Some(from_now(cooldown));
+    }
+
     pub(crate) fn timeslot_over_time(
         &self,
         config: &Config,
@@ -163,3 +305,11 @@ impl Tracker {
         false
     }
 }
+
+// Long config durations end at the last representable moment, instead of
+// overflowing
+fn from_now(duration: Duration) -> Timestamp {
+    Timestamp::now()
+        .checked_add(duration)
+        .unwrap_or(Timestamp::MAX)
+}
@@ -13,10 +13,10 @@ use log::{error, info, warn};
 use thiserror::Error;
 
 #[cfg(target_os = "windows")]
-#[cfg(feature = "deploy")]
+use windows::Win32::System::RemoteDesktop::WTSDisconnectSession;
+#[cfg(target_os = "windows")]
 use windows::Win32::System::RemoteDesktop::WTSLogoffSession;
 #[cfg(target_os = "windows")]
-#[cfg(feature = "deploy")]
 use windows::Win32::System::RemoteDesktop::WTS_CURRENT_SERVER_HANDLE;
 
 #[cfg(target_os = "windows")]
@@ -45,6 +45,15 @@ enum Error {
     #[cfg(target_os = "windows")]
     #[error("Error from Windows")]
     Windows(#[from] windows_core::Error),
+    #[cfg(target_os = "linux")]
+    #[error("User {0} doesn't exist")]
+    UserDoesntExist(String),
+    #[cfg(target_os = "linux")]
+    #[error("Group {0} doesn't exist")]
+    GroupDoesntExist(String),
+    #[cfg(target_os = "windows")]
+    #[error("Groups are not supported on Windows")]
+    GroupsUnsupported,
 }
 
 pub(crate) fn list_users() -> Result<Vec<String>> {
@@ -63,7 +72,6 @@ pub(crate) fn list_users() -> Result<Vec<String>> {
     users.collect()
 }
 
-#[cfg(feature = "deploy")]
 #[cfg(target_os = "linux")]
 pub(crate) fn logout(user: &str) {
     info!("Logging out user {user}");
@@ -87,7 +95,6 @@ pub(crate) fn logout(user: &str) {
     warn!("Reached maximum retries for logout");
 }
 
-#[cfg(feature = "deploy")]
 #[cfg(target_os = "windows")]
 /// Unsafe
 pub(crate) fn logout(user: &str) {
@@ -103,6 +110,44 @@ pub(crate) fn logout(user: &str) {
     }
 }
 
+pub(crate) fn lock(user: &str) {
+    if let Err(err) = lock_err(user) {
+        error!("Couldn't lock the sessions of {user}: {err}");
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn lock_err(user: &str) -> Result<(), Error> {
+    let output = Command::new("loginctl")
+        .arg("show-user")
+        .arg(user)
+        .arg("--property=Sessions")
+        .arg("--value")
+        .output()?;
+
+    for session in std::str::from_utf8(&output.stdout)?.split_whitespace() {
+        Command::new("loginctl")
+            .arg("lock-session")
+            .arg(session)
+            .output()?;
+    }
+    Ok(())
+}
+
+// Disconnecting a console session shows the lock screen
+#[cfg(target_os = "windows")]
+fn lock_err(user: &str) -> Result<(), Error> {
+    let active_consoles =
+        get_active_consoles().filter(|s| s.username == Some(user.to_string()));
+
+    for session in active_consoles {
+        unsafe {
+            WTSDisconnectSession(WTS_CURRENT_SERVER_HANDLE, session.id, false)?;
+        }
+    }
+    Ok(())
+}
+
 #[cfg(target_os = "windows")]
 fn get_active_consoles() -> impl Iterator<Item = session::Session> {
     session::get_sessions()
@@ -111,11 +156,6 @@ fn get_active_consoles() -> impl Iterator<Item = session::Session> {
         .filter(|s| s.station_name == "Console")
 }
 
-#[cfg(not(feature = "deploy"))]
-pub(crate) fn logout(user: &str) {
-    println!("Would log out user {user}, not deployed");
-}
-
 #[cfg(target_os = "linux")]
 pub(crate) fn exists(user: &str) -> bool {
     match fs::read_to_string("/etc/passwd") {
@@ -130,6 +170,70 @@ pub(crate) fn exists(user: &str) -> bool {
     }
 }
 
+#[cfg(target_os = "linux")]
+pub(crate) fn uid(user: &str) -> Result<u32> {
+    let passwd = fs::read_to_string("/etc/passwd")?;
+    let uid = passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.first() == Some(&user))
+        .and_then(|fields| fields.get(2)?.parse().ok())
+        .ok_or(Error::UserDoesntExist(user.to_owned()))?;
+
+    Ok(uid)
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn name(uid: u32) -> Result<String> {
+    let passwd = fs::read_to_string("/etc/passwd")?;
+    let name = passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.get(2) == Some(&uid.to_string().as_str()))
+        .map(|fields| fields[0].to_owned())
+        .ok_or(Error::UserDoesntExist(uid.to_string()))?;
+
+    Ok(name)
+}
+
+/// Members listed in /etc/group, plus users that have it as primary group
+#[cfg(target_os = "linux")]
+pub(crate) fn group_members(group: &str) -> Result<Vec<String>> {
+    let groups = fs::read_to_string("/etc/group")?;
+    let (gid, listed) = groups
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.first() == Some(&group))
+        .and_then(|fields| Some((*fields.get(2)?, *fields.get(3)?)))
+        .map(|(gid, listed)| (gid.to_owned(), listed.to_owned()))
+        .ok_or(Error::GroupDoesntExist(group.to_owned()))?;
+
+    let mut members: Vec<String> = listed
+        .split(',')
+        .filter(|member| !member.is_empty())
+        .map(ToOwned::to_owned)
+        .collect();
+
+    let passwd = fs::read_to_string("/etc/passwd")?;
+    for fields in passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+    {
+        if fields.get(3) == Some(&gid.as_str())
+            && !members.iter().any(|member| member == fields[0])
+        {
+            members.push(fields[0].to_owned());
+        }
+    }
+
+    Ok(members)
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn group_members(_group: &str) -> Result<Vec<String>> {
+    Err(Error::GroupsUnsupported)?
+}
+
 #[cfg(target_os = "windows")]
 pub(crate) fn exists(user: &str) -> bool {
     list_users().unwrap().contains(&user.to_owned())
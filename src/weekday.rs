@@ -0,0 +1,159 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use serde_derive::{Deserialize, Serialize};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use strum::{Display, VariantArray};
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Display,
+    VariantArray,
+)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    fn abbreviation(self) -> &'static str {
+        match self {
+            Weekday::Monday => "Mo",
+            Weekday::Tuesday => "Tu",
+            Weekday::Wednesday => "We",
+            Weekday::Thursday => "Th",
+            Weekday::Friday => "Fr",
+            Weekday::Saturday => "Sa",
+            Weekday::Sunday => "Su",
+        }
+    }
+
+    fn next(self) -> Self {
+        let index = Self::VARIANTS
+            .iter()
+            .position(|day| *day == self)
+            .expect("Every weekday is a variant");
+        Self::VARIANTS[(index + 1) % Self::VARIANTS.len()]
+    }
+}
+
+impl FromStr for Weekday {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mo" | "monday" => Ok(Self::Monday),
+            "tu" | "tuesday" => Ok(Self::Tuesday),
+            "we" | "wednesday" => Ok(Self::Wednesday),
+            "th" | "thursday" => Ok(Self::Thursday),
+            "fr" | "friday" => Ok(Self::Friday),
+            "sa" | "saturday" => Ok(Self::Saturday),
+            "su" | "sunday" => Ok(Self::Sunday),
+            invalid => Err(format!("Invalid weekday string: {invalid}")),
+        }
+    }
+}
+
+impl From<jiff::civil::Weekday> for Weekday {
+    fn from(value: jiff::civil::Weekday) -> Self {
+        match value {
+            jiff::civil::Weekday::Monday => Weekday::Monday,
+            jiff::civil::Weekday::Tuesday => Weekday::Tuesday,
+            jiff::civil::Weekday::Wednesday => Weekday::Wednesday,
+            jiff::civil::Weekday::Thursday => Weekday::Thursday,
+            jiff::civil::Weekday::Friday => Weekday::Friday,
+            jiff::civil::Weekday::Saturday => Weekday::Saturday,
+            jiff::civil::Weekday::Sunday => Weekday::Sunday,
+        }
+    }
+}
+
+/// A set of weekdays, written in the config as e.g. "Mo,We", "Mo-Fr",
+/// "weekdays" or "weekends" (and combinations like "weekdays,Sa")
+#[derive(
+    Debug, Clone, PartialEq, Eq, Hash, SerializeDisplay, DeserializeFromStr,
+)]
+pub struct Days(BTreeSet<Weekday>);
+
+impl Days {
+    pub fn all() -> Self {
+        Self(Weekday::VARIANTS.iter().copied().collect())
+    }
+
+    pub fn contains(&self, weekday: Weekday) -> bool {
+        self.0.contains(&weekday)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Weekday> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl FromStr for Days {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut days = BTreeSet::new();
+
+        for part in s.split(',').map(str::trim) {
+            match part.to_lowercase().as_str() {
+                "weekdays" => {
+                    days.extend(range(Weekday::Monday, Weekday::Friday));
+                }
+                "weekends" | "weekend" => {
+                    days.extend([Weekday::Saturday, Weekday::Sunday]);
+                }
+                _ => match part.split_once('-') {
+                    Some((first, last)) => {
+                        days.extend(range(
+                            first.trim().parse()?,
+                            last.trim().parse()?,
+                        ));
+                    }
+                    None => {
+                        days.insert(part.parse()?);
+                    }
+                },
+            }
+        }
+
+        Ok(Self(days))
+    }
+}
+
+impl fmt::Display for Days {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days: Vec<&str> = self.iter().map(Weekday::abbreviation).collect();
+        write!(f, "{}", days.join(","))
+    }
+}
+
+// Inclusive, wraps around the end of the week: Fr-Mo is Fr,Sa,Su,Mo
+fn range(first: Weekday, last: Weekday) -> Vec<Weekday> {
+    let mut days = vec![first];
+    let mut day = first;
+    while day != last {
+        day = day.next();
+        days.push(day);
+    }
+    days
+}